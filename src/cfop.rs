@@ -0,0 +1,304 @@
+use crate::cube::{Cube, Metric, Rotation};
+use crate::goal_pattern::{solve_to_pattern, GoalPattern};
+use crate::notation::parse_algorithm;
+use crate::solver::{SolveError, SolverConfig};
+
+
+// The 57 cases of orienting the last layer, by their usual numbers
+pub const OLL_ALGORITHMS: [(&str, &str); 57] = [
+    ("OLL 1", "R U2 R2 F R F' U2 R' F R F'"),
+    ("OLL 2", "F R U R' U' F' f R U R' U' f'"),
+    ("OLL 3", "f R U R' U' f' U' F R U R' U' F'"),
+    ("OLL 4", "f R U R' U' f' U F R U R' U' F'"),
+    ("OLL 5", "r' U2 R U R' U r"),
+    ("OLL 6", "r U2 R' U' R U' r'"),
+    ("OLL 7", "r U R' U R U2 r'"),
+    ("OLL 8", "r' U' R U' R' U2 r"),
+    ("OLL 9", "R U R' U' R' F R2 U R' U' F'"),
+    ("OLL 10", "R U R' U R' F R F' R U2 R'"),
+    ("OLL 11", "r U R' U R' F R F' R U2 r'"),
+    ("OLL 12", "M' R' U' R U' R' U2 R U' R r'"),
+    ("OLL 13", "F U R U' R2 F' R U R U' R'"),
+    ("OLL 14", "R' F R U R' F' R F U' F'"),
+    ("OLL 15", "r' U' r R' U' R U r' U r"),
+    ("OLL 16", "r U r' R U R' U' r U' r'"),
+    ("OLL 17", "F R' F' R2 r' U R U' R' U' M'"),
+    ("OLL 18", "r U R' U R U2 r2 U' R U' R' U2 r"),
+    ("OLL 19", "r' R U R U R' U' M' R' F R F'"),
+    ("OLL 20", "r U R' U' M2 U R U' R' U' M'"),
+    ("OLL 21", "R U2 R' U' R U R' U' R U' R'"),
+    ("OLL 22", "R U2 R2 U' R2 U' R2 U2 R"),
+    ("OLL 23", "R2 D' R U2 R' D R U2 R"),
+    ("OLL 24", "r U R' U' r' F R F'"),
+    ("OLL 25", "F' r U R' U' r' F R"),
+    ("OLL 26", "R U2 R' U' R U' R'"),
+    ("OLL 27", "R U R' U R U2 R'"),
+    ("OLL 28", "r U R' U' r' R U R U' R'"),
+    ("OLL 29", "R U R' U' R U' R' F' U' F R U R'"),
+    ("OLL 30", "F R' F R2 U' R' U' R U R' F2"),
+    ("OLL 31", "R' U' F U R U' R' F' R"),
+    ("OLL 32", "L U F' U' L' U L F L'"),
+    ("OLL 33", "R U R' U' R' F R F'"),
+    ("OLL 34", "R U R2 U' R' F R U R U' F'"),
+    ("OLL 35", "R U2 R2 F R F' R U2 R'"),
+    ("OLL 36", "L' U' L U' L' U L U L F' L' F"),
+    ("OLL 37", "F R' F' R U R U' R'"),
+    ("OLL 38", "R U R' U R U' R' U' R' F R F'"),
+    ("OLL 39", "L F' L' U' L U F U' L'"),
+    ("OLL 40", "R' F R U R' U' F' U R"),
+    ("OLL 41", "R U R' U R U2 R' F R U R' U' F'"),
+    ("OLL 42", "R' U' R U' R' U2 R F R U R' U' F'"),
+    ("OLL 43", "F' U' L' U L F"),
+    ("OLL 44", "F U R U' R' F'"),
+    ("OLL 45", "F R U R' U' F'"),
+    ("OLL 46", "R' U' R' F R F' U R"),
+    ("OLL 47", "R' U' R' F R F' R' F R F' U R"),
+    ("OLL 48", "F R U R' U' R U R' U' F'"),
+    ("OLL 49", "r U' r2 U r2 U r2 U' r"),
+    ("OLL 50", "r' U r2 U' r2 U' r2 U r'"),
+    ("OLL 51", "F U R U' R' U R U' R' F'"),
+    ("OLL 52", "R U R' U R U' B U' B' R'"),
+    ("OLL 53", "r' U' R U' R' U R U' R' U2 r"),
+    ("OLL 54", "r U R' U R U' R' U R U2 r'"),
+    ("OLL 55", "R U2 R2 U' R U' R' U2 F R F'"),
+    ("OLL 56", "r U r' U R U' R' U R U' R' r U' r'"),
+    ("OLL 57", "R U R' U' M' U R U' r'"),
+];
+
+// The 21 cases of permuting the last layer once it is oriented
+pub const PLL_ALGORITHMS: [(&str, &str); 21] = [
+    ("PLL Aa", "x R' U R' D2 R U' R' D2 R2 x'"),
+    ("PLL Ab", "x R2 D2 R U R' D2 R U' R x'"),
+    ("PLL E", "x' R U' R' D R U R' D' R U R' D R U' R' D' x"),
+    ("PLL F", "R' U' F' R U R' U' R' F R2 U' R' U' R U R' U R"),
+    ("PLL Ga", "R2 U R' U R' U' R U' R2 U' D R' U R D'"),
+    ("PLL Gb", "R' U' R U D' R2 U R' U R U' R U' R2 D"),
+    ("PLL Gc", "R2 U' R U' R U R' U R2 U D' R U' R' D"),
+    ("PLL Gd", "R U R' U' D R2 U' R U' R' U R' U R2 D'"),
+    ("PLL H", "M2 U M2 U2 M2 U M2"),
+    ("PLL Ja", "R' U L' U2 R U' R' U2 R L"),
+    ("PLL Jb", "R U R' F' R U R' U' R' F R2 U' R'"),
+    ("PLL Na", "R U R' U R U R' F' R U R' U' R' F R2 U' R' U2 R U' R'"),
+    ("PLL Nb", "R' U R U' R' F' U' F R U R' F R' F' R U' R"),
+    ("PLL Ra", "R U' R' U' R U R D R' U' R D' R' U2 R'"),
+    ("PLL Rb", "R2 F R U R U' R' F' R U2 R' U2 R"),
+    ("PLL T", "R U R' U' R' F R2 U' R' U' R U R' F'"),
+    ("PLL Ua", "M2 U M U2 M' U M2"),
+    ("PLL Ub", "M2 U' M U2 M' U' M2"),
+    ("PLL V", "R U' R U R' D R D' R U' D R2 U R2 D' R2"),
+    ("PLL Y", "F R U' R' U' R U R' F' R U R' U' R' F R F'"),
+    ("PLL Z", "M' U M2 U M2 U M' U2 M2"),
+];
+
+// The first two layers are solved a pair of a corner and an edge at a time,
+// each slot by the two sides next to it
+const F2L_SLOTS: [(&str, u8, u8); 4] = [
+    ("F2L FR", 4, 3),
+    ("F2L FL", 4, 1),
+    ("F2L BL", 2, 1),
+    ("F2L BR", 2, 3),
+];
+
+const AUF: [&[Rotation]; 4] = [&[], &[Rotation::U], &[Rotation::U2], &[Rotation::Ur]];
+
+
+// The cross and the pairs of the slots solved so far, `sides` are the sides
+// of the stickers of a piece on the solved cube
+fn is_solved_piece(sides: &[u8], slots: &[(u8, u8)]) -> bool {
+    let cross = sides.len() == 2 && sides.contains(&0);
+    cross || slots.iter().any(|(a, b)| sides.contains(a) && sides.contains(b) && !sides.contains(&5))
+}
+
+fn centers_home(cube: &Cube) -> bool {
+    (0..6).all(|side| cube.get_at(side, 4) == side as u8)
+}
+
+// The first two layers solved and every sticker of the U face on it
+fn is_oriented(cube: &Cube, first_two_layers: &GoalPattern) -> bool {
+    centers_home(cube) && first_two_layers.matches(cube) && (0..9).all(|face| cube.get_at(5, face) == 5)
+}
+
+fn is_solved_but_auf(cube: &Cube) -> bool {
+    AUF.iter().any(|auf| cube.rotate_all(auf) == Cube::new())
+}
+
+// The algorithm of a table and a turn of the U layer before it that get the
+// cube to where `done` holds, the turn of U is part of the moves returned
+fn find_algorithm(cube: &Cube, table: &[(&str, &str)], done: impl Fn(&Cube) -> bool) -> Option<(String, Vec<Rotation>)> {
+    for (name, algorithm) in table.iter() {
+        let rotations = parse_algorithm(algorithm)
+            .unwrap_or_else(|error| panic!("the algorithm of {} does not parse: {}", name, error));
+        for auf in AUF.iter() {
+            let mut moves = auf.to_vec();
+            moves.extend(rotations.iter().cloned());
+            if done(&cube.rotate_all(&moves)) {
+                return Some((name.to_string(), moves))
+            }
+        }
+    }
+    None
+}
+
+
+// A solve the way a person does it with CFOP: the D cross (as short as
+// possible), the four pairs of the first two layers one at a time (the one
+// with the shortest solution first, without turning D), then orienting and
+// permuting the last layer with the algorithms of OLL_ALGORITHMS and
+// PLL_ALGORITHMS. Returns the name and the moves of every step. The config
+// limits the searches for the cross and the pairs.
+pub fn solve_cube_cfop(start_cube: &Cube, config: &SolverConfig) -> Result<Vec<(String, Vec<Rotation>)>, SolveError> {
+    let mut steps = vec![];
+    let cross = solve_to_pattern(start_cube, &GoalPattern::cross(), Metric::HalfTurn, config)?;
+    let mut cube = start_cube.rotate_all(&cross);
+    steps.push(("CROSS".to_string(), cross));
+
+    let pair_config = config.clone().with_moves(&[Rotation::U, Rotation::R, Rotation::L, Rotation::F, Rotation::B]);
+    let mut solved_slots: Vec<(u8, u8)> = vec![];
+    while solved_slots.len() < F2L_SLOTS.len() {
+        let mut best: Option<(&str, (u8, u8), Vec<Rotation>)> = None;
+        for (name, a, b) in F2L_SLOTS.iter() {
+            if solved_slots.contains(&(*a, *b)) {
+                continue;
+            }
+            let mut slots = solved_slots.clone();
+            slots.push((*a, *b));
+            let pattern = GoalPattern::pieces(|sides| is_solved_piece(sides, &slots));
+            let pair = solve_to_pattern(&cube, &pattern, Metric::HalfTurn, &pair_config)?;
+            if best.as_ref().is_none_or(|(_, _, best_pair)| pair.len() < best_pair.len()) {
+                best = Some((name, (*a, *b), pair));
+            }
+        }
+        let (name, slot, pair) = best.expect("a slot of the first two layers is left to solve");
+        cube = cube.rotate_all(&pair);
+        solved_slots.push(slot);
+        steps.push((name.to_string(), pair));
+    }
+
+    let first_two_layers = GoalPattern::first_two_layers();
+    let oriented = |cube: &Cube| is_oriented(cube, &first_two_layers);
+    if oriented(&cube) {
+        steps.push(("OLL skip".to_string(), vec![]));
+    } else {
+        let (name, rotations) = find_algorithm(&cube, &OLL_ALGORITHMS, oriented).ok_or(SolveError::Unsolvable)?;
+        cube = cube.rotate_all(&rotations);
+        steps.push((name, rotations));
+    }
+
+    if is_solved_but_auf(&cube) {
+        steps.push(("PLL skip".to_string(), vec![]));
+    } else {
+        let (name, rotations) = find_algorithm(&cube, &PLL_ALGORITHMS, is_solved_but_auf).ok_or(SolveError::Unsolvable)?;
+        cube = cube.rotate_all(&rotations);
+        steps.push((name, rotations));
+    }
+    let auf = AUF.iter()
+        .find(|auf| cube.rotate_all(auf) == Cube::new())
+        .expect("the last layer is solved but for a turn of U after PLL");
+    if !auf.is_empty() {
+        steps.push(("AUF".to_string(), auf.to_vec()));
+    }
+
+    Ok(steps)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::coordinates::{is_odd_permutation, permutation_from_rank};
+    use crate::cube::Key;
+    use crate::cubie::CubieCube;
+
+    // Every way to twist the corners and flip the edges of the U layer (the
+    // first four corners and edges of a cubie cube), 27 * 8
+    fn last_layer_orientations() -> Vec<CubieCube> {
+        let mut cubes = vec![];
+        for twists in 0..27 {
+            for flips in 0..8 {
+                let mut cube = CubieCube::new();
+                for position in 0..3 {
+                    cube.corner_orientation[position] = (twists / 3usize.pow(position as u32) % 3) as u8;
+                    cube.edge_orientation[position] = (flips >> position & 1) as u8;
+                }
+                cube.corner_orientation[3] = (6 - cube.corner_orientation[..3].iter().sum::<u8>()) % 3;
+                cube.edge_orientation[3] = cube.edge_orientation[..3].iter().sum::<u8>() % 2;
+                cubes.push(cube);
+            }
+        }
+        cubes
+    }
+
+    // Every way to place the corners and edges of the U layer, 24 * 24 / 2
+    fn last_layer_permutations() -> Vec<CubieCube> {
+        let mut cubes = vec![];
+        for corners in 0..24 {
+            for edges in 0..24 {
+                let mut cube = CubieCube::new();
+                permutation_from_rank(corners, &mut cube.corner_permutation[..4]);
+                permutation_from_rank(edges, &mut cube.edge_permutation[..4]);
+                if is_odd_permutation(&cube.corner_permutation) == is_odd_permutation(&cube.edge_permutation) {
+                    cubes.push(cube);
+                }
+            }
+        }
+        cubes
+    }
+
+    // The same key for a case seen from any side and with U turned before it
+    fn case_key(cube: &CubieCube) -> Key {
+        AUF.iter().flat_map(|before| AUF.iter().map(move |after| {
+            CubieCube::new().rotate_all(before).multiply(cube).rotate_all(after).to_cube().get_key()
+        })).min().unwrap()
+    }
+
+    // Finds an algorithm for every case that is not done yet and returns how
+    // many cases there are and the names of the algorithms used
+    fn solve_cases(cubes: &[CubieCube], table: &[(&str, &str)], done: impl Fn(&Cube) -> bool) -> (usize, HashSet<String>) {
+        let mut cases = HashSet::new();
+        let mut names = HashSet::new();
+        for cube in cubes.iter().map(|cube| cube.to_cube()) {
+            if done(&cube) {
+                continue;
+            }
+            let (name, moves) = find_algorithm(&cube, table, &done)
+                .unwrap_or_else(|| panic!("no algorithm for {:?}", CubieCube::from_cube(&cube).unwrap()));
+            assert!(done(&cube.rotate_all(&moves)));
+            cases.insert(case_key(&CubieCube::from_cube(&cube).unwrap()));
+            names.insert(name);
+        }
+        (cases.len(), names)
+    }
+
+    #[test]
+    fn every_oll_case_is_covered() {
+        let first_two_layers = GoalPattern::first_two_layers();
+        let cubes = last_layer_orientations();
+        assert_eq!(cubes.len(), 216);
+        let (cases, names) = solve_cases(&cubes, &OLL_ALGORITHMS, |cube| is_oriented(cube, &first_two_layers));
+        assert_eq!(cases, 57);
+        assert_eq!(names.len(), OLL_ALGORITHMS.len());
+    }
+
+    #[test]
+    fn every_pll_case_is_covered() {
+        let cubes = last_layer_permutations();
+        assert_eq!(cubes.len(), 288);
+        let (cases, names) = solve_cases(&cubes, &PLL_ALGORITHMS, is_solved_but_auf);
+        assert_eq!(cases, 21);
+        assert_eq!(names.len(), PLL_ALGORITHMS.len());
+    }
+
+    #[test]
+    fn solves_step_by_step() {
+        let scramble = parse_algorithm("R U2 F' D L2 B R' U F2 D' L B2 U R F' D2").unwrap();
+        let start_cube = Cube::new().rotate_all(&scramble);
+        let steps = solve_cube_cfop(&start_cube, &SolverConfig::new()).unwrap();
+        assert_eq!(steps[0].0, "CROSS");
+        assert!(GoalPattern::cross().matches(&start_cube.rotate_all(&steps[0].1)));
+        let names: Vec<&str> = steps[1..5].iter().map(|(name, _)| name.as_str()).collect();
+        assert!(F2L_SLOTS.iter().all(|(slot, _, _)| names.contains(slot)));
+        let solution: Vec<Rotation> = steps.iter().flat_map(|(_, rotations)| rotations.clone()).collect();
+        assert_eq!(start_cube.rotate_all(&solution), Cube::new());
+    }
+}
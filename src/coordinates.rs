@@ -0,0 +1,178 @@
+use crate::cubie::CubieCube;
+
+
+pub(crate) fn is_odd_permutation(permutation: &[u8]) -> bool {
+    let mut inversions = 0;
+    for i in 0..permutation.len() {
+        for j in i + 1..permutation.len() {
+            if permutation[i] > permutation[j] {
+                inversions += 1;
+            }
+        }
+    }
+    inversions % 2 == 1
+}
+
+// Rank of a permutation of 0..n in lexicographic order
+pub(crate) fn permutation_rank(permutation: &[u8]) -> usize {
+    let mut rank = 0;
+    for i in 0..permutation.len() {
+        let smaller = permutation[i + 1..].iter().filter(|other| **other < permutation[i]).count();
+        rank = rank * (permutation.len() - i) + smaller;
+    }
+    rank
+}
+
+pub(crate) fn permutation_from_rank(mut rank: usize, permutation: &mut [u8]) {
+    let n = permutation.len();
+    let mut digits = vec![0; n];
+    for i in (0..n).rev() {
+        digits[i] = rank % (n - i);
+        rank /= n - i;
+    }
+    let mut unused: Vec<u8> = (0..n as u8).collect();
+    for i in 0..n {
+        permutation[i] = unused.remove(digits[i]);
+    }
+}
+
+// Rank of where the pieces of a group are, given as distinct positions out of
+// `positions`, e.g. 12 * 11 * 10 * 9 * 8 * 7 ranks for six edges
+pub(crate) fn arrangement_rank(arrangement: &[u8], positions: usize) -> usize {
+    let mut rank = 0;
+    for i in 0..arrangement.len() {
+        let smaller = (0..arrangement[i]).filter(|position| !arrangement[..i].contains(position)).count();
+        rank = rank * (positions - i) + smaller;
+    }
+    rank
+}
+
+pub(crate) fn arrangement_from_rank(mut rank: usize, positions: usize, arrangement: &mut [u8]) {
+    let n = arrangement.len();
+    let mut digits = vec![0; n];
+    for i in (0..n).rev() {
+        digits[i] = rank % (positions - i);
+        rank /= positions - i;
+    }
+    let mut unused: Vec<u8> = (0..positions as u8).collect();
+    for i in 0..n {
+        arrangement[i] = unused.remove(digits[i]);
+    }
+}
+
+
+impl CubieCube {
+    // 0..2187, the twists of the first seven corners as base 3 digits. The last
+    // twist follows from the others since they add up to a multiple of 3.
+    pub fn twist(&self) -> u16 {
+        self.corner_orientation[..7].iter().fold(0, |twist, orientation| twist * 3 + *orientation as u16)
+    }
+
+    pub fn set_twist(&mut self, mut twist: u16) {
+        let mut total = 0;
+        for i in (0..7).rev() {
+            self.corner_orientation[i] = (twist % 3) as u8;
+            total += self.corner_orientation[i];
+            twist /= 3;
+        }
+        self.corner_orientation[7] = (3 - total % 3) % 3;
+    }
+
+    // 0..2048, like the twist but for the edge flips
+    pub fn flip(&self) -> u16 {
+        self.edge_orientation[..11].iter().fold(0, |flip, orientation| flip * 2 + *orientation as u16)
+    }
+
+    pub fn set_flip(&mut self, mut flip: u16) {
+        let mut total = 0;
+        for i in (0..11).rev() {
+            self.edge_orientation[i] = (flip % 2) as u8;
+            total += self.edge_orientation[i];
+            flip /= 2;
+        }
+        self.edge_orientation[11] = total % 2;
+    }
+
+    // 0..40320
+    pub fn corner_permutation_rank(&self) -> u16 {
+        permutation_rank(&self.corner_permutation) as u16
+    }
+
+    pub fn set_corner_permutation_rank(&mut self, rank: u16) {
+        permutation_from_rank(rank as usize, &mut self.corner_permutation);
+    }
+}
+
+// Binomial coefficient n over k, 0 when k > n
+pub(crate) fn binomial(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |result, i| result * (n - i) / (i + 1))
+}
+
+impl CubieCube {
+    // 0..495, which four positions hold the FR, FL, BL and BR edges in any
+    // order. Solved is 494.
+    pub fn slice(&self) -> u16 {
+        let mut slice = 0;
+        let mut found = 0;
+        for position in 0..12 {
+            if self.edge_permutation[position] >= 8 {
+                found += 1;
+                slice += binomial(position, found);
+            }
+        }
+        slice as u16
+    }
+
+    pub fn set_slice(&mut self, slice: u16) {
+        let mut slice = slice as usize;
+        let mut positions = [false; 12];
+        for found in (1..5).rev() {
+            let mut position = found - 1;
+            while binomial(position + 1, found) <= slice {
+                position += 1;
+            }
+            slice -= binomial(position, found);
+            positions[position] = true;
+        }
+        let mut slice_edge = 8;
+        let mut other_edge = 0;
+        for (position, is_slice) in positions.iter().enumerate() {
+            if *is_slice {
+                self.edge_permutation[position] = slice_edge;
+                slice_edge += 1;
+            } else {
+                self.edge_permutation[position] = other_edge;
+                other_edge += 1;
+            }
+        }
+    }
+
+    // 0..40320, the order of the eight U and D layer edges. Only meaningful
+    // while they are all in the U and D layers.
+    pub fn ud_edge_permutation_rank(&self) -> u16 {
+        permutation_rank(&self.edge_permutation[..8]) as u16
+    }
+
+    pub fn set_ud_edge_permutation_rank(&mut self, rank: u16) {
+        permutation_from_rank(rank as usize, &mut self.edge_permutation[..8]);
+        self.edge_permutation[8..].copy_from_slice(&[8, 9, 10, 11]);
+    }
+
+    // 0..24, the order of the four slice edges while they are in the slice
+    pub fn slice_permutation_rank(&self) -> u16 {
+        let slice_edges = self.edge_permutation[8..].iter().map(|edge| edge - 8).collect::<Vec<u8>>();
+        permutation_rank(&slice_edges) as u16
+    }
+
+    pub fn set_slice_permutation_rank(&mut self, rank: u16) {
+        let mut slice_edges = [0; 4];
+        permutation_from_rank(rank as usize, &mut slice_edges);
+        self.edge_permutation = [0, 1, 2, 3, 4, 5, 6, 7, 0, 0, 0, 0];
+        for (i, edge) in slice_edges.iter().enumerate() {
+            self.edge_permutation[8 + i] = edge + 8;
+        }
+    }
+}
@@ -1,24 +1,29 @@
 use std::collections::HashMap;
-use colored::Colorize;
 use rand::Rng;
+use crate::cubie::CubieCube;
+use crate::simplify::{decompose, simplify};
+use crate::subgroup::Subgroup;
+use crate::symmetry::{conjugate_rotation, Symmetry};
+use crate::solver::{SearchLimits, SolveError, SolverConfig};
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Side {
     pub colors: [u8; 8],
+    pub center: u8,
 }
 
 impl Side {
     fn new_full(color: u8) -> Side {
-        Side {colors: [color; 8]}
+        Side {colors: [color; 8], center: color}
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Cube {
     sides: [Side; 6],
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Rotation {
     U,
     D,
@@ -32,11 +37,91 @@ pub enum Rotation {
     Lr,
     Fr,
     Br,
+    U2,
+    D2,
+    R2,
+    L2,
+    F2,
+    B2,
+    M,
+    E,
+    S,
+    Mr,
+    Er,
+    Sr,
+    M2,
+    E2,
+    S2,
+    X,
+    Y,
+    Z,
+    Xr,
+    Yr,
+    Zr,
+    X2,
+    Y2,
+    Z2,
+    Uw,
+    Dw,
+    Rw,
+    Lw,
+    Fw,
+    Bw,
+    Uwr,
+    Dwr,
+    Rwr,
+    Lwr,
+    Fwr,
+    Bwr,
+    Uw2,
+    Dw2,
+    Rw2,
+    Lw2,
+    Fw2,
+    Bw2,
+}
+
+
+// Which turns count as a single move when searching for the shortest solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    QuarterTurn,
+    HalfTurn,
+}
+
+impl Metric {
+    pub fn rotations(&self) -> Vec<Rotation> {
+        let mut rotations = vec![
+            Rotation::U,
+            Rotation::D,
+            Rotation::R,
+            Rotation::L,
+            Rotation::F,
+            Rotation::B,
+            Rotation::Ur,
+            Rotation::Dr,
+            Rotation::Rr,
+            Rotation::Lr,
+            Rotation::Fr,
+            Rotation::Br,
+        ];
+        if *self == Metric::HalfTurn {
+            rotations.extend([
+                Rotation::U2,
+                Rotation::D2,
+                Rotation::R2,
+                Rotation::L2,
+                Rotation::F2,
+                Rotation::B2,
+            ]);
+        }
+        rotations
+    }
 }
 
 
 impl Rotation {
-    fn reverse(&self) -> Rotation {
+    pub fn reverse(&self) -> Rotation {
         match self {
             Rotation::U => Rotation::Ur,
             Rotation::D => Rotation::Dr,
@@ -50,11 +135,60 @@ impl Rotation {
             Rotation::Lr => Rotation::L,
             Rotation::Fr => Rotation::F,
             Rotation::Br => Rotation::B,
+            Rotation::U2 => Rotation::U2,
+            Rotation::D2 => Rotation::D2,
+            Rotation::R2 => Rotation::R2,
+            Rotation::L2 => Rotation::L2,
+            Rotation::F2 => Rotation::F2,
+            Rotation::B2 => Rotation::B2,
+            Rotation::M => Rotation::Mr,
+            Rotation::E => Rotation::Er,
+            Rotation::S => Rotation::Sr,
+            Rotation::Mr => Rotation::M,
+            Rotation::Er => Rotation::E,
+            Rotation::Sr => Rotation::S,
+            Rotation::M2 => Rotation::M2,
+            Rotation::E2 => Rotation::E2,
+            Rotation::S2 => Rotation::S2,
+            Rotation::X => Rotation::Xr,
+            Rotation::Y => Rotation::Yr,
+            Rotation::Z => Rotation::Zr,
+            Rotation::Xr => Rotation::X,
+            Rotation::Yr => Rotation::Y,
+            Rotation::Zr => Rotation::Z,
+            Rotation::X2 => Rotation::X2,
+            Rotation::Y2 => Rotation::Y2,
+            Rotation::Z2 => Rotation::Z2,
+            Rotation::Uw => Rotation::Uwr,
+            Rotation::Dw => Rotation::Dwr,
+            Rotation::Rw => Rotation::Rwr,
+            Rotation::Lw => Rotation::Lwr,
+            Rotation::Fw => Rotation::Fwr,
+            Rotation::Bw => Rotation::Bwr,
+            Rotation::Uwr => Rotation::Uw,
+            Rotation::Dwr => Rotation::Dw,
+            Rotation::Rwr => Rotation::Rw,
+            Rotation::Lwr => Rotation::Lw,
+            Rotation::Fwr => Rotation::Fw,
+            Rotation::Bwr => Rotation::Bw,
+            Rotation::Uw2 => Rotation::Uw2,
+            Rotation::Dw2 => Rotation::Dw2,
+            Rotation::Rw2 => Rotation::Rw2,
+            Rotation::Lw2 => Rotation::Lw2,
+            Rotation::Fw2 => Rotation::Fw2,
+            Rotation::Bw2 => Rotation::Bw2,
         }
     }
 }
 
 
+impl Default for Cube {
+    fn default() -> Cube {
+        Cube::new()
+    }
+}
+
+
 impl Cube {
     pub fn new() -> Cube {
         Cube {
@@ -70,19 +204,11 @@ impl Cube {
     }
 
     pub fn new_shuffled(shuffles: u32) -> Cube {
-        let mut rng = rand::thread_rng();
-        let mut new = Cube::new();
-        for _ in 0..shuffles {
-            let rotations = new.get_rotations();
-            // Choose random rotation
-            let random_index = (rng.gen::<f64>() * rotations.len() as f64) as usize;
-            let rotation = rotations[random_index].clone();
-            new = rotation;
-            // new = new.rotate(&rotation);
-        }
-        new
+        Cube::new().rotate_all(&random_scramble(shuffles))
     }
 
+    // Every sticker but the centers gets a color of its own, from 0 to 47, to
+    // follow stickers around. These colors cannot be searched for or keyed.
     pub fn new_debug() -> Cube {
         let mut new = Cube::new();
         for i in 0..6 {
@@ -98,30 +224,87 @@ impl Cube {
             // Match different variations of rotations
 
             Rotation::U => self.rotate_4_r(),
-            Rotation::D => self.rotate_3_r(),
+            Rotation::D => self.rotate_3(),
             Rotation::R => self.rotate_2(),
             Rotation::L => self.rotate_1_r(),
             Rotation::F => self.rotate_6(),
             Rotation::B => self.rotate_5_r(),
             Rotation::Ur => self.rotate_4(),
-            Rotation::Dr => self.rotate_3(),
+            Rotation::Dr => self.rotate_3_r(),
             Rotation::Rr => self.rotate_2_r(),
             Rotation::Lr => self.rotate_1(),
             Rotation::Fr => self.rotate_6_r(),
             Rotation::Br => self.rotate_5(),
+            Rotation::U2 => self.rotate_4_r().rotate_4_r(),
+            Rotation::D2 => self.rotate_3().rotate_3(),
+            Rotation::R2 => self.rotate_2().rotate_2(),
+            Rotation::L2 => self.rotate_1_r().rotate_1_r(),
+            Rotation::F2 => self.rotate_6().rotate_6(),
+            Rotation::B2 => self.rotate_5_r().rotate_5_r(),
+
+            // Slices turn like the face they follow: M like L, E like D and S like F
+            Rotation::M => self.rotate_m(),
+            Rotation::E => self.rotate_e(),
+            Rotation::S => self.rotate_s(),
+            Rotation::Mr => self.rotate_m_r(),
+            Rotation::Er => self.rotate_e_r(),
+            Rotation::Sr => self.rotate_s_r(),
+            Rotation::M2 => self.rotate_m().rotate_m(),
+            Rotation::E2 => self.rotate_e().rotate_e(),
+            Rotation::S2 => self.rotate_s().rotate_s(),
+
+            // Whole cube rotations turn like R, U and F: x = R M' L', y = U E' D', z = F S B'
+            Rotation::X => self.rotate_2().rotate_m_r().rotate_1(),
+            Rotation::Y => self.rotate_4_r().rotate_e_r().rotate_3_r(),
+            Rotation::Z => self.rotate_6().rotate_s().rotate_5(),
+            Rotation::Xr => self.rotate_2_r().rotate_m().rotate_1_r(),
+            Rotation::Yr => self.rotate_4().rotate_e().rotate_3(),
+            Rotation::Zr => self.rotate_6_r().rotate_s_r().rotate_5_r(),
+            Rotation::X2 => self.rotate(&Rotation::X).rotate(&Rotation::X),
+            Rotation::Y2 => self.rotate(&Rotation::Y).rotate(&Rotation::Y),
+            Rotation::Z2 => self.rotate(&Rotation::Z).rotate(&Rotation::Z),
+
+            // Wide moves turn the outer face together with the slice next to it
+            Rotation::Uw => self.rotate_4_r().rotate_e_r(),
+            Rotation::Dw => self.rotate_3().rotate_e(),
+            Rotation::Rw => self.rotate_2().rotate_m_r(),
+            Rotation::Lw => self.rotate_1_r().rotate_m(),
+            Rotation::Fw => self.rotate_6().rotate_s(),
+            Rotation::Bw => self.rotate_5_r().rotate_s_r(),
+            Rotation::Uwr => self.rotate_4().rotate_e(),
+            Rotation::Dwr => self.rotate_3_r().rotate_e_r(),
+            Rotation::Rwr => self.rotate_2_r().rotate_m(),
+            Rotation::Lwr => self.rotate_1().rotate_m_r(),
+            Rotation::Fwr => self.rotate_6_r().rotate_s_r(),
+            Rotation::Bwr => self.rotate_5().rotate_s(),
+            Rotation::Uw2 => self.rotate(&Rotation::Uw).rotate(&Rotation::Uw),
+            Rotation::Dw2 => self.rotate(&Rotation::Dw).rotate(&Rotation::Dw),
+            Rotation::Rw2 => self.rotate(&Rotation::Rw).rotate(&Rotation::Rw),
+            Rotation::Lw2 => self.rotate(&Rotation::Lw).rotate(&Rotation::Lw),
+            Rotation::Fw2 => self.rotate(&Rotation::Fw).rotate(&Rotation::Fw),
+            Rotation::Bw2 => self.rotate(&Rotation::Bw).rotate(&Rotation::Bw),
+        }
+    }
+
+    pub fn rotate_all(&self, rotations: &[Rotation]) -> Cube {
+        let mut cube = self.clone();
+        for rotation in rotations.iter() {
+            cube = cube.rotate(rotation);
         }
+        cube
     }
 
     pub fn get_at(&self, side: usize, face: usize) -> u8 {
         if face == 4 {
-            panic!("Cannot set center piece")
+            return self.sides[side].center
         }
         let face_pos = if face < 4 {face} else {face - 1};
         self.sides[side].colors[face_pos]
     }
     pub fn set_at(&mut self, side: usize, face: usize, color: u8) {
         if face == 4 {
-            panic!("Cannot set center piece")
+            self.sides[side].center = color;
+            return
         }
         let face_pos = if face < 4 {face} else {face - 1};
         self.sides[side].colors[face_pos] = color;
@@ -487,208 +670,739 @@ impl Cube {
         new_cube
     }
 
-    fn get_rotations(&self) -> Vec<Cube> {
-        let mut rotations = Vec::new();
-        rotations.push(self.rotate_1());
-        rotations.push(self.rotate_2());
-        rotations.push(self.rotate_3());
-        rotations.push(self.rotate_4());
-        rotations.push(self.rotate_5());
-        rotations.push(self.rotate_6());
-        rotations.push(self.rotate_1_r());
-        rotations.push(self.rotate_2_r());
-        rotations.push(self.rotate_3_r());
-        rotations.push(self.rotate_4_r());
-        rotations.push(self.rotate_5_r());
-        rotations.push(self.rotate_6_r());
-        rotations
+    fn rotate_m(&self) -> Cube {
+        let mut new_cube = self.clone();
+        new_cube.set_at(0, 7, self.get_at(4, 1));
+        new_cube.set_at(0, 4, self.get_at(4, 4));
+        new_cube.set_at(0, 1, self.get_at(4, 7));
+
+        new_cube.set_at(4, 7, self.get_at(5, 1));
+        new_cube.set_at(4, 4, self.get_at(5, 4));
+        new_cube.set_at(4, 1, self.get_at(5, 7));
+
+        new_cube.set_at(5, 1, self.get_at(2, 1));
+        new_cube.set_at(5, 4, self.get_at(2, 4));
+        new_cube.set_at(5, 7, self.get_at(2, 7));
+
+        new_cube.set_at(2, 1, self.get_at(0, 1));
+        new_cube.set_at(2, 4, self.get_at(0, 4));
+        new_cube.set_at(2, 7, self.get_at(0, 7));
+
+        new_cube
     }
 
-    fn get_hash(&self) -> Hash {
-        let mut hash: Hash = [0; 3];
-        let mut index = 0;
-        for i in 0..9 {
-            for j in 0..6 {
-                if i == 4 {
-                    continue;
-                }
-                let value = self.get_at(j, i);
-                if index <= 20 {
-                    let fixed_index = index as u64;
-                    hash[0] += (value as u64).rotate_left(3 * fixed_index as u32);
-                } else if index <= 39 {
-                    let fixed_index = (index - 20) as u64;
-                    hash[1] += (value as u64).rotate_left(3 * fixed_index as u32);
+    fn rotate_e(&self) -> Cube {
+        let mut new_cube = self.clone();
+        new_cube.set_at(1, 3, self.get_at(2, 3));
+        new_cube.set_at(1, 4, self.get_at(2, 4));
+        new_cube.set_at(1, 5, self.get_at(2, 5));
+
+        new_cube.set_at(2, 3, self.get_at(3, 3));
+        new_cube.set_at(2, 4, self.get_at(3, 4));
+        new_cube.set_at(2, 5, self.get_at(3, 5));
+
+        new_cube.set_at(3, 3, self.get_at(4, 3));
+        new_cube.set_at(3, 4, self.get_at(4, 4));
+        new_cube.set_at(3, 5, self.get_at(4, 5));
+
+        new_cube.set_at(4, 3, self.get_at(1, 3));
+        new_cube.set_at(4, 4, self.get_at(1, 4));
+        new_cube.set_at(4, 5, self.get_at(1, 5));
+
+        new_cube
+    }
+
+    fn rotate_s(&self) -> Cube {
+        let mut new_cube = self.clone();
+        new_cube.set_at(0, 3, self.get_at(3, 1));
+        new_cube.set_at(0, 4, self.get_at(3, 4));
+        new_cube.set_at(0, 5, self.get_at(3, 7));
+
+        new_cube.set_at(3, 7, self.get_at(5, 3));
+        new_cube.set_at(3, 4, self.get_at(5, 4));
+        new_cube.set_at(3, 1, self.get_at(5, 5));
+
+        new_cube.set_at(5, 3, self.get_at(1, 1));
+        new_cube.set_at(5, 4, self.get_at(1, 4));
+        new_cube.set_at(5, 5, self.get_at(1, 7));
+
+        new_cube.set_at(1, 7, self.get_at(0, 3));
+        new_cube.set_at(1, 4, self.get_at(0, 4));
+        new_cube.set_at(1, 1, self.get_at(0, 5));
+
+        new_cube
+    }
+
+    fn rotate_m_r(&self) -> Cube {
+        let mut new_cube = self.clone();
+        new_cube.set_at(0, 1, self.get_at(2, 1));
+        new_cube.set_at(0, 4, self.get_at(2, 4));
+        new_cube.set_at(0, 7, self.get_at(2, 7));
+
+        new_cube.set_at(2, 1, self.get_at(5, 1));
+        new_cube.set_at(2, 4, self.get_at(5, 4));
+        new_cube.set_at(2, 7, self.get_at(5, 7));
+
+        new_cube.set_at(5, 7, self.get_at(4, 1));
+        new_cube.set_at(5, 4, self.get_at(4, 4));
+        new_cube.set_at(5, 1, self.get_at(4, 7));
+
+        new_cube.set_at(4, 7, self.get_at(0, 1));
+        new_cube.set_at(4, 4, self.get_at(0, 4));
+        new_cube.set_at(4, 1, self.get_at(0, 7));
+
+        new_cube
+    }
+
+    fn rotate_e_r(&self) -> Cube {
+        let mut new_cube = self.clone();
+        new_cube.set_at(1, 3, self.get_at(4, 3));
+        new_cube.set_at(1, 4, self.get_at(4, 4));
+        new_cube.set_at(1, 5, self.get_at(4, 5));
+
+        new_cube.set_at(4, 3, self.get_at(3, 3));
+        new_cube.set_at(4, 4, self.get_at(3, 4));
+        new_cube.set_at(4, 5, self.get_at(3, 5));
+
+        new_cube.set_at(3, 3, self.get_at(2, 3));
+        new_cube.set_at(3, 4, self.get_at(2, 4));
+        new_cube.set_at(3, 5, self.get_at(2, 5));
+
+        new_cube.set_at(2, 3, self.get_at(1, 3));
+        new_cube.set_at(2, 4, self.get_at(1, 4));
+        new_cube.set_at(2, 5, self.get_at(1, 5));
+
+        new_cube
+    }
+
+    fn rotate_s_r(&self) -> Cube {
+        let mut new_cube = self.clone();
+        new_cube.set_at(0, 5, self.get_at(1, 1));
+        new_cube.set_at(0, 4, self.get_at(1, 4));
+        new_cube.set_at(0, 3, self.get_at(1, 7));
+
+        new_cube.set_at(1, 1, self.get_at(5, 3));
+        new_cube.set_at(1, 4, self.get_at(5, 4));
+        new_cube.set_at(1, 7, self.get_at(5, 5));
+
+        new_cube.set_at(5, 5, self.get_at(3, 1));
+        new_cube.set_at(5, 4, self.get_at(3, 4));
+        new_cube.set_at(5, 3, self.get_at(3, 7));
+
+        new_cube.set_at(3, 1, self.get_at(0, 3));
+        new_cube.set_at(3, 4, self.get_at(0, 4));
+        new_cube.set_at(3, 7, self.get_at(0, 5));
+
+        new_cube
+    }
+
+    // Every sticker as a base 6 digit, 6^48 fits in a u128. Only cubes with the
+    // colors 0 to 5 have a key, not the cubes of new_debug.
+    // The centers are kept apart so cubes held differently never share a key.
+    pub(crate) fn get_key(&self) -> Key {
+        let mut stickers: u128 = 0;
+        let mut centers: u16 = 0;
+        for side in 0..6 {
+            for face in 0..9 {
+                let value = self.get_at(side, face);
+                debug_assert!(value < 6, "color {} has no key digit", value);
+                if face == 4 {
+                    centers = centers * 6 + value as u16;
                 } else {
-                    let fixed_index = (index - 39) as u64;
-                    hash[2] += (value as u64).rotate_left(3 * fixed_index as u32)
+                    stickers = stickers * 6 + value as u128;
                 }
-                index += 1;
             }
         }
-        hash
+        (stickers, centers)
+    }
+}
+
+
+// Random quarter turns with cancelling and mergeable moves simplified away,
+// so the scramble can be shorter than the number of moves asked for
+pub fn random_scramble(moves: u32) -> Vec<Rotation> {
+    let mut rng = rand::thread_rng();
+    let all_rotations = Metric::QuarterTurn.rotations();
+    let mut rotations = vec![];
+    for _ in 0..moves {
+        // Choose random rotation
+        let random_index = (rng.gen::<f64>() * all_rotations.len() as f64) as usize;
+        rotations.push(all_rotations[random_index].clone());
+    }
+    simplify(&rotations)
+}
+
+
+pub(crate) type Key = (u128, u16);
+
+
+// The move that reached each cube and how many moves from where the search
+// started it is, keyed by the symmetry class of the cube. Split in shards by
+// key so each thread can fill a shard of its own.
+pub(crate) struct Visited {
+    shards: Vec<HashMap<Key, (Option<Rotation>, u8)>>,
+}
+
+impl Visited {
+    fn new(shards: usize) -> Visited {
+        Visited { shards: (0..shards.max(1)).map(|_| HashMap::new()).collect() }
+    }
+
+    fn shard(&self, key: &Key) -> usize {
+        shard_of(key, self.shards.len())
+    }
+
+    pub(crate) fn get(&self, key: &Key) -> Option<&(Option<Rotation>, u8)> {
+        self.shards[self.shard(key)].get(key)
+    }
+
+    pub(crate) fn contains_key(&self, key: &Key) -> bool {
+        self.shards[self.shard(key)].contains_key(key)
+    }
+
+    fn insert(&mut self, key: Key, value: (Option<Rotation>, u8)) {
+        let shard = self.shard(&key);
+        self.shards[shard].insert(key, value);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    fn capacity(&self) -> usize {
+        self.shards.iter().map(|shard| shard.capacity()).sum()
     }
 }
 
+impl std::ops::Index<&Key> for Visited {
+    type Output = (Option<Rotation>, u8);
 
-type Hash = [u64; 3];
+    fn index(&self, key: &Key) -> &(Option<Rotation>, u8) {
+        self.get(key).unwrap()
+    }
+}
+
+fn shard_of(key: &Key, shards: usize) -> usize {
+    let mixed = (key.0 ^ (key.0 >> 64)) as u64 ^ key.1 as u64;
+    (mixed.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize % shards
+}
 
 
-fn get_solution_from_hashmap(
-    middle_hash: Hash,
-    middle_cube: &Cube,
-    hash_map: &HashMap<Hash, Option<Rotation>>,
-    reverse: bool
-) -> Option<Vec<Rotation>> {
+// Follows the moves stored for the cubes back to where the search started.
+// Only works from a cube the search stored itself, not from a conjugate.
+fn get_solution_from_start(middle_cube: &Cube, start_keys: &Visited, symmetries: &[&Symmetry]) -> Vec<Rotation> {
     let mut solution_rotations: Vec<Rotation> = vec![];
+    let mut lookup_cube = middle_cube.clone();
+    while let (Some(rotation), _) = &start_keys[&lookup_cube.symmetry_key(symmetries)] {
+        lookup_cube = lookup_cube.rotate(&rotation.reverse());
+        solution_rotations.push(rotation.clone());
+    }
+    solution_rotations.reverse();
+    solution_rotations
+}
 
-    // Find route forward
+// Moves on to a cube one move closer to the end cube each time. Every cube
+// of a class is as far from the end cube, which all the symmetries leave as
+// it is, so this works from any cube of a class the search has reached.
+fn get_solution_to_end(middle_cube: &Cube, end_keys: &Visited, symmetries: &[&Symmetry], rotations: &[Rotation]) -> Vec<Rotation> {
+    let mut solution_rotations: Vec<Rotation> = vec![];
     let mut lookup_cube = middle_cube.clone();
-    let mut lookup_hash = middle_hash;
-    loop {
-        let lookup_rotation = hash_map.get(&lookup_hash).unwrap();
-        match lookup_rotation {
-            Some(rotation) => {
-                lookup_cube = lookup_cube.rotate(&rotation.reverse());
-                lookup_hash = lookup_cube.get_hash();
-                solution_rotations.push(if reverse {rotation.reverse()} else {rotation.clone()});
-            },
-            None => {
-                break
+    let mut depth = end_keys[&lookup_cube.symmetry_key(symmetries)].1;
+    while depth > 0 {
+        for rotation in rotations.iter() {
+            let rotated_cube = lookup_cube.rotate(rotation);
+            if end_keys.get(&rotated_cube.symmetry_key(symmetries)).is_some_and(|(_, other)| *other == depth - 1) {
+                lookup_cube = rotated_cube;
+                solution_rotations.push(rotation.clone());
+                break;
             }
         }
+        depth -= 1;
     }
-    Some(solution_rotations)
+    solution_rotations
 }
 
+// Axis, layer and net quarter turns of the last layer turned on the way to a
+// cube, None for the cubes the searches start from
+pub(crate) type LastTurn = Option<(usize, usize, u8)>;
+
+// Sequences that cannot be shortest, or have an equally long twin that is
+// searched instead: turning the layer just turned again (except U U in the
+// quarter turn metric, counted as U2 afterwards) and turning opposite faces
+// in the other order, e.g. D U is skipped for U D.
+// Symmetries turn U U into U' U' and U D into D U, so when cubes are kept
+// by symmetry class only the sequences that are never shortest are skipped.
+pub(crate) fn is_redundant(last_turn: LastTurn, turn: (usize, usize, u8), metric: Metric, ordered: bool) -> bool {
+    let Some((last_axis, last_layer, last_quarter_turns)) = last_turn else {
+        return false
+    };
+    let (axis, layer, quarter_turns) = turn;
+    if axis != last_axis {
+        return false
+    }
+    if layer == last_layer {
+        if metric == Metric::HalfTurn || last_quarter_turns == 2 {
+            return true
+        }
+        if ordered {
+            return last_quarter_turns != 1 || quarter_turns != 1
+        }
+        return (last_quarter_turns + quarter_turns) % 4 == 0
+    }
+    ordered && layer < last_layer
+}
 
-fn get_solution_from_two_way_hashmaps(
-    middle_hash: Hash,
-    middle_cube: &Cube,
-    begin_hashes: &HashMap<Hash, Option<Rotation>>,
-    end_hashes: &HashMap<Hash, Option<Rotation>>
-) -> Option<Vec<Rotation>> {
-    let mut solution_rotations: Vec<Rotation> = vec![];
+pub(crate) fn next_last_turn(last_turn: LastTurn, turn: (usize, usize, u8)) -> LastTurn {
+    match last_turn {
+        Some((axis, layer, quarter_turns)) if (axis, layer) == (turn.0, turn.1) => {
+            Some((axis, layer, (quarter_turns + turn.2) % 4))
+        },
+        _ => Some(turn),
+    }
+}
 
-    let forward_solution = get_solution_from_hashmap(middle_hash, middle_cube, end_hashes, false).unwrap();
-    for rotation in forward_solution.iter().rev() {
-        solution_rotations.push(rotation.clone());
+// What a side of the search stores: each cube once in `keys` and the cubes
+// reached last in the frontier
+fn stored_bytes(keys: &Visited, frontier: usize) -> usize {
+    keys.capacity() * (size_of::<(Key, (Option<Rotation>, u8))>() + 1) + frontier * size_of::<(Cube, LastTurn)>()
+}
+
+// The symmetries of the end cube that turn the moves searched with into each
+// other, only those keep every cube of a class as far from the end cube
+pub(crate) fn search_symmetries(end_cube: &Cube, rotations: &[Rotation]) -> Vec<&'static Symmetry> {
+    end_cube.symmetries().into_iter().filter(|symmetry| {
+        rotations.iter().all(|rotation| rotations.contains(&conjugate_rotation(rotation, symmetry)))
+    }).collect()
+}
+
+// The searches from both cubes, keeping one cube of each class of cubes the
+// symmetries of the end cube turn into each other (48 for the solved cube),
+// they are all as far from the end cube
+struct BreadthFirstSearch<'a> {
+    symmetries: Vec<&'static Symmetry>,
+    rotations: Vec<Rotation>,
+    turns: Vec<(usize, usize, u8)>,
+    metric: Metric,
+    limits: &'a SearchLimits<'a>,
+}
+
+// A cube reached by turning a cube of the frontier, `order` is its place
+// among the cubes reached from the same chunk of the frontier
+struct Candidate {
+    order: usize,
+    key: Key,
+    cube: Cube,
+    rotation: Rotation,
+    last_turn: LastTurn,
+}
+
+// The candidates a shard kept as (chunk, order, shard, index in the shard)
+// and the first one that met the other search as (chunk, order, key)
+type ShardResult = (Vec<(usize, usize, usize, usize)>, Option<(usize, usize, Key)>);
+
+// Smaller chunks of the frontier are not worth a thread
+const MIN_CHUNK: usize = 256;
+
+impl<'a> BreadthFirstSearch<'a> {
+    // With the moves the config allows
+    fn new(end_cube: &Cube, metric: Metric, limits: &'a SearchLimits<'a>) -> Result<BreadthFirstSearch<'a>, SolveError> {
+        let rotations = limits.config.rotations(metric)?;
+        Ok(BreadthFirstSearch {
+            symmetries: search_symmetries(end_cube, &rotations),
+            turns: rotations.iter().map(decompose).collect(),
+            rotations,
+            metric,
+            limits,
+        })
     }
 
-    let backward_solution = get_solution_from_hashmap(middle_hash, middle_cube, begin_hashes, true).unwrap();
-    for rotation in backward_solution.iter() {
-        solution_rotations.push(rotation.clone());
+    // Returns a cube both searches reached, if they met, and the number of
+    // moves tried, a plain search tries every move from every cube.
+    // The frontier is turned in chunks on `config.threads` threads, then each
+    // shard of `keys` keeps the first cube of every class in the order a
+    // single thread would have found them, so the result does not depend on
+    // the number of threads.
+    fn extend(
+        &self,
+        old_cubes: &[(Cube, LastTurn)],
+        new_cubes: &mut Vec<(Cube, LastTurn)>,
+        keys: &mut Visited,
+        other_keys: &Visited,
+        depth: u8,
+    ) -> Result<(Option<Cube>, usize), SolveError> {
+        let threads = self.limits.config.threads.max(1);
+        let chunk_size = old_cubes.len().div_ceil(threads).max(MIN_CHUNK);
+        let chunk_count = old_cubes.len().div_ceil(chunk_size);
+
+        let stored_keys: &Visited = keys;
+        let chunks = std::thread::scope(|scope| {
+            let handles: Vec<_> = old_cubes.chunks(chunk_size).map(|chunk| {
+                scope.spawn(move || self.turn_chunk(chunk, stored_keys, other_keys, chunk_count))
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Result<Vec<_>, _>>()
+        })?;
+        let tried = chunks.iter().map(|(_, tried)| tried).sum();
+
+        let shard_results: Vec<ShardResult> = std::thread::scope(|scope| {
+            let handles: Vec<_> = keys.shards.iter_mut().enumerate().map(|(shard, visited)| {
+                let chunks = &chunks;
+                scope.spawn(move || {
+                    let mut kept = vec![];
+                    for (chunk_index, (chunk, _)) in chunks.iter().enumerate() {
+                        for (index, candidate) in chunk[shard].iter().enumerate() {
+                            if visited.contains_key(&candidate.key) {
+                                continue;
+                            }
+                            visited.insert(candidate.key, (Some(candidate.rotation.clone()), depth));
+                            if other_keys.contains_key(&candidate.key) {
+                                return (kept, Some((chunk_index, candidate.order, candidate.key)))
+                            }
+                            kept.push((chunk_index, candidate.order, shard, index));
+                        }
+                    }
+                    (kept, None)
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        let met = shard_results.iter().filter_map(|(_, met)| *met).min_by_key(|(chunk, order, _)| (*chunk, *order));
+        if let Some((chunk, _, key)) = met {
+            let shard = shard_of(&key, keys.shards.len());
+            let candidate = chunks[chunk].0[shard].iter().find(|candidate| candidate.key == key).unwrap();
+            return Ok((Some(candidate.cube.clone()), tried))
+        }
+
+        let mut kept: Vec<(usize, usize, usize, usize)> = shard_results.into_iter().flat_map(|(kept, _)| kept).collect();
+        kept.sort_unstable();
+        new_cubes.extend(kept.into_iter().map(|(chunk, _, shard, index)| {
+            let candidate = &chunks[chunk].0[shard][index];
+            (candidate.cube.clone(), candidate.last_turn)
+        }));
+        let bytes = stored_bytes(keys, old_cubes.len() + new_cubes.len()) + stored_bytes(other_keys, 0);
+        self.limits.check(keys.len() + other_keys.len(), bytes)?;
+
+        Ok((None, tried))
     }
 
-    Some(solution_rotations)
+    // Turns every cube of a chunk, the cubes not stored yet are returned by
+    // the shard their key belongs to
+    fn turn_chunk(
+        &self,
+        chunk: &[(Cube, LastTurn)],
+        keys: &Visited,
+        other_keys: &Visited,
+        chunk_count: usize,
+    ) -> Result<(Vec<Vec<Candidate>>, usize), SolveError> {
+        let ordered = self.symmetries.len() <= 1;
+        let mut candidates: Vec<Vec<Candidate>> = (0..keys.shards.len()).map(|_| vec![]).collect();
+        let mut found = 0;
+        let mut tried = 0;
+
+        for (cube, last_turn) in chunk.iter() {
+            // Every chunk is guessed to have found as many cubes as this one
+            let nodes = keys.len() + other_keys.len() + found * chunk_count;
+            let bytes = stored_bytes(keys, found * chunk_count) + stored_bytes(other_keys, 0)
+                + found * chunk_count * size_of::<(Key, (Option<Rotation>, u8))>();
+            self.limits.check(nodes, bytes)?;
+            for (rotation, turn) in self.rotations.iter().zip(self.turns.iter()) {
+                if is_redundant(*last_turn, *turn, self.metric, ordered) {
+                    continue;
+                }
+                tried += 1;
+                let rotated_cube = cube.rotate(rotation);
+                let key = rotated_cube.symmetry_key(&self.symmetries);
+                if keys.contains_key(&key) {
+                    continue;
+                }
+                candidates[keys.shard(&key)].push(Candidate {
+                    order: tried,
+                    key,
+                    cube: rotated_cube,
+                    rotation: rotation.clone(),
+                    last_turn: next_last_turn(*last_turn, *turn),
+                });
+                found += 1;
+            }
+        }
+
+        Ok((candidates, tried))
+    }
 }
 
-fn extend_breath_first_search(
-    old_cubes: & Vec<Cube>,
-    new_cubes: &mut Vec<Cube>,
-    hashes: &mut HashMap<Hash, Option<Rotation>>,
-    other_hashes: &HashMap<Hash, Option<Rotation>>,
-) -> Option<Vec<Rotation>> {
-    let all_rotations: Vec<Rotation> = vec![
-        Rotation::U,
-        Rotation::D,
-        Rotation::R,
-        Rotation::L,
-        Rotation::F,
-        Rotation::B,
-        Rotation::Ur,
-        Rotation::Dr,
-        Rotation::Rr,
-        Rotation::Lr,
-        Rotation::Fr,
-        Rotation::Br,
-    ];
-
-    let mut hash_collisions = 0;
-
-    for cube in old_cubes.iter() {
-        let rotations = cube.get_rotations();
-        for rotation in all_rotations.iter() {
-            // for rotation in rotations {
-            let rotated_cube = cube.rotate(&rotation);
-            let hash = rotated_cube.get_hash();
-            if hashes.contains_key(&hash) {
-                hash_collisions += 1;
-                continue;
-            }
-            hashes.insert(hash, Some(rotation.clone()));
-            if other_hashes.contains_key(&hash) {
-                let solution_rotations = get_solution_from_two_way_hashmaps(
-                    hash,
-                    &rotated_cube,
-                    &hashes,
-                    &other_hashes,
-                ).unwrap();
-                return Some(solution_rotations)
+// Searches from both cubes a move at a time until they meet, so the solution
+// is as short as possible. Stops with an error when `config` limits are hit.
+pub fn solve_cube_two_way_breath_first(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    metric: Metric,
+    config: &SolverConfig,
+) -> Result<Vec<Rotation>, SolveError> {
+    start_cube.validate()?;
+    end_cube.validate()?;
+    if (0..6).any(|side| start_cube.get_at(side, 4) != end_cube.get_at(side, 4)) {
+        return Err(SolveError::Unsolvable)
+    }
+    if config.moves.is_some() {
+        let subgroup = Subgroup::new(&config.rotations(metric)?);
+        if let (Some(start), Some(end)) = (CubieCube::from_cube(start_cube), CubieCube::from_cube(end_cube)) {
+            if !subgroup.connects(&start, &end) {
+                return Err(SolveError::Unsolvable)
             }
-            new_cubes.push(rotated_cube);
         }
     }
-
-    return None
+    two_way_search(start_cube, end_cube, metric, config)
 }
 
-fn rotations_reversed(rotations: &Vec<Rotation>) -> Vec<Rotation> {
-    let mut reversed_rotations: Vec<Rotation> = vec![];
-    for rotation in rotations.iter().rev() {
-        reversed_rotations.push(rotation.reverse());
+// The search itself, for any stickers as long as there are at most 6 colors,
+// like the recolored cubes of a goal pattern
+pub(crate) fn two_way_search(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    metric: Metric,
+    config: &SolverConfig,
+) -> Result<Vec<Rotation>, SolveError> {
+    if start_cube == end_cube {
+        return Ok(vec![])
     }
-    reversed_rotations
-}
 
-pub fn solve_cube_two_way_breath_first(start_cube: &Cube, end_cube: &Cube) -> Option<Vec<Rotation>> {
-    let mut a_hashes: HashMap<Hash, Option<Rotation>> = HashMap::new();
-    let mut a_old_cubes: Vec<Cube> = vec![end_cube.clone()];
-    let mut a_new_cubes: Vec<Cube> = Vec::new();
-    a_hashes.insert(end_cube.get_hash(), None);
-
-    let mut b_hashes: HashMap<Hash, Option<Rotation>> = HashMap::new();
-    let mut b_old_cubes: Vec<Cube> = vec![start_cube.clone()];
-    let mut b_new_cubes: Vec<Cube> = Vec::new();
-    b_hashes.insert(start_cube.get_hash(), None);
-
-    for _ in 0..10 {
-        // 1 step of front
-        let found_solution = extend_breath_first_search(
-            &a_old_cubes,
-            &mut a_new_cubes,
-            &mut a_hashes,
-            &b_hashes,
-        );
-        println!("Front step {} {} {}", a_old_cubes.len(), a_new_cubes.len(), a_hashes.len());
-        match found_solution {
-            Some(solution) => {
-                println!("FOUND SOLUTION REVERSE");
-                return Some(solution)
-            },
-            None => {}
+    let limits = SearchLimits::new(config);
+    let search = BreadthFirstSearch::new(end_cube, metric, &limits)?;
+    let symmetries = &search.symmetries;
+
+    let mut a_keys = Visited::new(config.threads);
+    let mut a_old_cubes: Vec<(Cube, LastTurn)> = vec![(end_cube.clone(), None)];
+    let mut a_new_cubes: Vec<(Cube, LastTurn)> = Vec::new();
+    a_keys.insert(end_cube.symmetry_key(symmetries), (None, 0));
+
+    let mut b_keys = Visited::new(config.threads);
+    let mut b_old_cubes: Vec<(Cube, LastTurn)> = vec![(start_cube.clone(), None)];
+    let mut b_new_cubes: Vec<(Cube, LastTurn)> = Vec::new();
+    b_keys.insert(start_cube.symmetry_key(symmetries), (None, 0));
+
+    let mut depth = 0;
+    loop {
+        // 1 step of front, solutions found now are 2 * depth + 1 moves long
+        if 2 * depth + 1 > config.max_depth {
+            return Err(SolveError::DepthLimit { depth: config.max_depth })
+        }
+        let (found_cube, tried) = search.extend(&a_old_cubes, &mut a_new_cubes, &mut a_keys, &b_keys, depth as u8 + 1)?;
+        if config.verbose {
+            println!(
+                "Front step {} {} {} branching {:.2}",
+                a_old_cubes.len(), a_new_cubes.len(), a_keys.len(), tried as f64 / a_old_cubes.len() as f64,
+            );
+        }
+        if let Some(found_cube) = found_cube {
+            // The search from the start cube reached the class last step,
+            // continue from the cube it stored
+            let key = found_cube.symmetry_key(symmetries);
+            let (middle_cube, _) = b_old_cubes.iter().find(|(cube, _)| cube.symmetry_key(symmetries) == key).unwrap();
+            let mut solution = get_solution_from_start(middle_cube, &b_keys, symmetries);
+            solution.extend(get_solution_to_end(middle_cube, &a_keys, symmetries, &search.rotations));
+            return Ok(solution)
+        }
+        if a_new_cubes.is_empty() {
+            return Err(SolveError::Unsolvable)
         }
         a_old_cubes = a_new_cubes;
         a_new_cubes = Vec::new();
 
-        // 1 step of back
-        let found_solution = extend_breath_first_search(
-            &b_old_cubes,
-            &mut b_new_cubes,
-            &mut b_hashes,
-            &a_hashes,
-        );
-        println!("Back step {} {} {}", b_old_cubes.len(), b_new_cubes.len(), b_hashes.len());
-        match found_solution {
-            Some(solution) => {
-                println!("FOUND SOLUTION");
-                return Some(rotations_reversed(&solution))
-            },
-            None => {}
+        // 1 step of back, solutions found now are 2 * depth + 2 moves long
+        if 2 * depth + 2 > config.max_depth {
+            return Err(SolveError::DepthLimit { depth: config.max_depth })
+        }
+        let (found_cube, tried) = search.extend(&b_old_cubes, &mut b_new_cubes, &mut b_keys, &a_keys, depth as u8 + 1)?;
+        if config.verbose {
+            println!(
+                "Back step {} {} {} branching {:.2}",
+                b_old_cubes.len(), b_new_cubes.len(), b_keys.len(), tried as f64 / b_old_cubes.len() as f64,
+            );
+        }
+        if let Some(middle_cube) = found_cube {
+            let mut solution = get_solution_from_start(&middle_cube, &b_keys, symmetries);
+            solution.extend(get_solution_to_end(&middle_cube, &a_keys, symmetries, &search.rotations));
+            return Ok(solution)
+        }
+        if b_new_cubes.is_empty() {
+            return Err(SolveError::Unsolvable)
         }
         b_old_cubes = b_new_cubes;
         b_new_cubes = Vec::new();
+        depth += 1;
+    }
+}
+
+
+// How far the cubes up to `depth` moves from the end cube are from it, by
+// symmetry class like the two way search stores them
+pub(crate) fn distances_to_end(
+    end_cube: &Cube,
+    metric: Metric,
+    depth: usize,
+    limits: &SearchLimits,
+) -> Result<Visited, SolveError> {
+    let search = BreadthFirstSearch::new(end_cube, metric, limits)?;
+    let mut keys = Visited::new(limits.config.threads);
+    keys.insert(end_cube.symmetry_key(&search.symmetries), (None, 0));
+    let nothing = Visited::new(1);
+    let mut old_cubes: Vec<(Cube, LastTurn)> = vec![(end_cube.clone(), None)];
+    for step in 1..=depth {
+        let mut new_cubes = Vec::new();
+        search.extend(&old_cubes, &mut new_cubes, &mut keys, &nothing, step as u8)?;
+        old_cubes = new_cubes;
     }
+    Ok(keys)
+}
 
-    None
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn d_turns_front_to_right() {
+        // Clockwise seen from below, the row of F next to D moves to R
+        let cube = Cube::new().rotate(&Rotation::D);
+        for face in 0..3 {
+            assert_eq!(cube.get_at(3, face), 4);
+            assert_eq!(cube.get_at(4, face), 1);
+        }
+        assert_eq!(cube.rotate(&Rotation::Dr), Cube::new());
+    }
+
+    #[test]
+    fn half_turns_are_two_quarter_turns() {
+        for (half, quarter) in [(Rotation::U2, Rotation::U), (Rotation::D2, Rotation::D), (Rotation::R2, Rotation::R),
+                                (Rotation::L2, Rotation::L), (Rotation::F2, Rotation::F), (Rotation::B2, Rotation::B)] {
+            assert_eq!(Cube::new().rotate(&half), Cube::new().rotate(&quarter).rotate(&quarter));
+            assert_eq!(half.reverse(), half);
+        }
+    }
+
+    #[test]
+    fn metric_decides_what_a_move_is() {
+        assert_eq!(Metric::QuarterTurn.rotations().len(), 12);
+        assert_eq!(Metric::HalfTurn.rotations().len(), 18);
+        let start_cube = Cube::new().rotate(&Rotation::R2);
+        let config = SolverConfig::new();
+        assert_eq!(solve_cube_two_way_breath_first(&start_cube, &Cube::new(), Metric::HalfTurn, &config).unwrap(), vec![Rotation::R2]);
+        assert_eq!(solve_cube_two_way_breath_first(&start_cube, &Cube::new(), Metric::QuarterTurn, &config).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn slices_and_rotations_follow_the_faces() {
+        let turned = |algorithm: &str| Cube::new().rotate_all(&crate::notation::parse_algorithm(algorithm).unwrap());
+        // M follows L, E follows D and S follows F
+        assert_eq!(turned("M"), turned("R L' x'"));
+        assert_eq!(turned("E"), turned("U D' y'"));
+        assert_eq!(turned("S"), turned("F' B z"));
+        assert_eq!(turned("x x x x"), Cube::new());
+        assert_eq!(turned("y2 z2"), turned("x2"));
+        // x takes the F center up
+        assert_eq!(turned("x").get_at(5, 4), 4);
+    }
+
+    #[test]
+    fn wide_moves_turn_the_slice_along() {
+        let turned = |algorithm: &str| Cube::new().rotate_all(&crate::notation::parse_algorithm(algorithm).unwrap());
+        assert_eq!(turned("Rw"), turned("R M'"));
+        assert_eq!(turned("Lw"), turned("L M"));
+        assert_eq!(turned("Uw'"), turned("U' E"));
+        assert_eq!(turned("Dw2"), turned("D2 E2"));
+        assert_eq!(turned("Fw"), turned("F S"));
+        assert_eq!(turned("Bw"), turned("B S'"));
+        assert_eq!(turned("Rw L'"), turned("x"));
+    }
+
+    #[test]
+    fn keys_tell_every_cube_apart() {
+        let mut cubes = vec![Cube::new()];
+        for rotation in Metric::HalfTurn.rotations() {
+            cubes.push(Cube::new().rotate(&rotation));
+        }
+        // Held differently the solved cube is another cube
+        for rotation in [Rotation::X, Rotation::Y, Rotation::Z] {
+            cubes.push(Cube::new().rotate(&rotation));
+        }
+        let keys: std::collections::HashSet<Key> = cubes.iter().map(|cube| cube.get_key()).collect();
+        assert_eq!(keys.len(), cubes.len());
+
+        let turned = Cube::new().rotate_all(&[Rotation::R, Rotation::U, Rotation::Rr, Rotation::Ur]);
+        let same = Cube::new().rotate_all(&[Rotation::R, Rotation::U, Rotation::Rr, Rotation::Ur]);
+        assert_eq!(turned.get_key(), same.get_key());
+        assert_ne!(turned.get_key(), Cube::new().get_key());
+    }
+
+    // Cubes at the end of every sequence of `depth` moves that is not redundant
+    fn unpruned_cubes(metric: Metric, ordered: bool, depth: usize) -> Vec<Cube> {
+        let mut cubes: Vec<(Cube, LastTurn)> = vec![(Cube::new(), None)];
+        for _ in 0..depth {
+            cubes = cubes.iter().flat_map(|(cube, last_turn)| {
+                metric.rotations().into_iter().filter_map(move |rotation| {
+                    let turn = decompose(&rotation);
+                    if is_redundant(*last_turn, turn, metric, ordered) {
+                        return None
+                    }
+                    Some((cube.rotate(&rotation), next_last_turn(*last_turn, turn)))
+                })
+            }).collect();
+        }
+        cubes.into_iter().map(|(cube, _)| cube).collect()
+    }
+
+    #[test]
+    fn pruning_keeps_one_sequence_per_cube() {
+        // Up to 3 half turn metric moves every cube has only one shortest sequence
+        let counts: Vec<usize> = (1..=3).map(|depth| unpruned_cubes(Metric::HalfTurn, true, depth).len()).collect();
+        assert_eq!(counts, vec![18, 243, 3240]);
+    }
+
+    // How many cubes (or classes of cubes) the breadth first search finds at
+    // each depth from the solved cube
+    fn layer_sizes(metric: Metric, with_symmetries: bool, depth: usize) -> Vec<usize> {
+        let config = SolverConfig::new();
+        let limits = SearchLimits::new(&config);
+        let mut search = BreadthFirstSearch::new(&Cube::new(), metric, &limits).unwrap();
+        if !with_symmetries {
+            search.symmetries.clear();
+        }
+        let mut keys = Visited::new(config.threads);
+        keys.insert(Cube::new().symmetry_key(&search.symmetries), (None, 0));
+        let nothing = Visited::new(1);
+        let mut old_cubes: Vec<(Cube, LastTurn)> = vec![(Cube::new(), None)];
+        let mut sizes = vec![];
+        for step in 1..=depth {
+            let mut new_cubes = Vec::new();
+            search.extend(&old_cubes, &mut new_cubes, &mut keys, &nothing, step as u8).unwrap();
+            sizes.push(new_cubes.len());
+            old_cubes = new_cubes;
+        }
+        sizes
+    }
+
+    #[test]
+    fn pruning_still_reaches_every_cube() {
+        // https://oeis.org/A080602 and https://oeis.org/A080601
+        assert_eq!(layer_sizes(Metric::QuarterTurn, false, 4), vec![12, 114, 1068, 10011]);
+        assert_eq!(layer_sizes(Metric::HalfTurn, false, 4), vec![18, 243, 3240, 43239]);
+        assert_eq!(layer_sizes(Metric::QuarterTurn, true, 4), vec![1, 5, 25, 219]);
+        assert_eq!(layer_sizes(Metric::HalfTurn, true, 4), vec![2, 9, 75, 934]);
+    }
+
+    #[test]
+    fn threads_find_the_same_solution() {
+        let start_cube = Cube::new().rotate_all(&crate::notation::parse_algorithm("R U2 F' L D B'").unwrap());
+        for metric in [Metric::QuarterTurn, Metric::HalfTurn] {
+            let solve = |threads: usize| {
+                let config = SolverConfig::new().with_threads(threads);
+                solve_cube_two_way_breath_first(&start_cube, &Cube::new(), metric, &config).unwrap()
+            };
+            let solution = solve(1);
+            assert_eq!(start_cube.rotate_all(&solution), Cube::new());
+            for threads in [2, 3, 8] {
+                assert_eq!(solve(threads), solution);
+            }
+        }
+    }
 }
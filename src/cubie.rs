@@ -0,0 +1,290 @@
+use crate::cube::{Cube, Rotation};
+use crate::facelets::FACE_NAMES;
+
+
+pub const CORNER_NAMES: [&str; 8] = ["URF", "UFL", "ULB", "UBR", "DFR", "DLF", "DBL", "DRB"];
+pub const EDGE_NAMES: [&str; 12] = ["UR", "UF", "UL", "UB", "DR", "DF", "DL", "DB", "FR", "FL", "BL", "BR"];
+
+// Facelet indices (0 = U1, 9 = R1 ...) of each corner and edge position. The
+// first facelet of a piece is the one its orientation is counted from.
+pub(crate) const CORNER_FACELETS: [[usize; 3]; 8] = [
+    [8, 9, 20], [6, 18, 38], [0, 36, 47], [2, 45, 11],
+    [29, 26, 15], [27, 44, 24], [33, 53, 42], [35, 17, 51],
+];
+pub(crate) const EDGE_FACELETS: [[usize; 2]; 12] = [
+    [5, 10], [7, 19], [3, 37], [1, 46], [32, 16], [28, 25],
+    [30, 43], [34, 52], [23, 12], [21, 41], [50, 39], [48, 14],
+];
+
+// Faces (in URFDLB order) of each piece's facelets in the solved cube
+pub(crate) const CORNER_FACES: [[usize; 3]; 8] = [
+    [0, 1, 2], [0, 2, 4], [0, 4, 5], [0, 5, 1],
+    [3, 2, 1], [3, 4, 2], [3, 5, 4], [3, 1, 5],
+];
+pub(crate) const EDGE_FACES: [[usize; 2]; 12] = [
+    [0, 1], [0, 2], [0, 4], [0, 5], [3, 1], [3, 2],
+    [3, 4], [3, 5], [2, 1], [2, 4], [5, 4], [5, 1],
+];
+
+
+// The cube as pieces: which corner and edge is at each position and how it is
+// twisted or flipped there. Positions and pieces are numbered like CORNER_NAMES
+// and EDGE_NAMES, a twist of 1 means the reference facelet was turned clockwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CubieCube {
+    pub corner_permutation: [u8; 8],
+    pub corner_orientation: [u8; 8],
+    pub edge_permutation: [u8; 12],
+    pub edge_orientation: [u8; 12],
+}
+
+impl Default for CubieCube {
+    fn default() -> CubieCube {
+        CubieCube::new()
+    }
+}
+
+
+// The six face turns, U R F D L B
+const FACE_TURNS: [CubieCube; 6] = [
+    CubieCube {
+        corner_permutation: [3, 0, 1, 2, 4, 5, 6, 7],
+        corner_orientation: [0, 0, 0, 0, 0, 0, 0, 0],
+        edge_permutation: [3, 0, 1, 2, 4, 5, 6, 7, 8, 9, 10, 11],
+        edge_orientation: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    },
+    CubieCube {
+        corner_permutation: [4, 1, 2, 0, 7, 5, 6, 3],
+        corner_orientation: [2, 0, 0, 1, 1, 0, 0, 2],
+        edge_permutation: [8, 1, 2, 3, 11, 5, 6, 7, 4, 9, 10, 0],
+        edge_orientation: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    },
+    CubieCube {
+        corner_permutation: [1, 5, 2, 3, 0, 4, 6, 7],
+        corner_orientation: [1, 2, 0, 0, 2, 1, 0, 0],
+        edge_permutation: [0, 9, 2, 3, 4, 8, 6, 7, 1, 5, 10, 11],
+        edge_orientation: [0, 1, 0, 0, 0, 1, 0, 0, 1, 1, 0, 0],
+    },
+    CubieCube {
+        corner_permutation: [0, 1, 2, 3, 5, 6, 7, 4],
+        corner_orientation: [0, 0, 0, 0, 0, 0, 0, 0],
+        edge_permutation: [0, 1, 2, 3, 5, 6, 7, 4, 8, 9, 10, 11],
+        edge_orientation: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    },
+    CubieCube {
+        corner_permutation: [0, 2, 6, 3, 4, 1, 5, 7],
+        corner_orientation: [0, 1, 2, 0, 0, 2, 1, 0],
+        edge_permutation: [0, 1, 10, 3, 4, 5, 9, 7, 8, 2, 6, 11],
+        edge_orientation: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    },
+    CubieCube {
+        corner_permutation: [0, 1, 3, 7, 4, 5, 2, 6],
+        corner_orientation: [0, 0, 1, 2, 0, 0, 2, 1],
+        edge_permutation: [0, 1, 2, 11, 4, 5, 6, 10, 8, 9, 3, 7],
+        edge_orientation: [0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 1],
+    },
+];
+
+
+// Face (in URFDLB order) of every facelet, judged by the centers. Colors no
+// center has are given as 6.
+pub(crate) fn facelet_faces(cube: &Cube) -> [usize; 54] {
+    let mut faces = [6; 54];
+    for (facelet, letter) in cube.to_facelets().chars().enumerate() {
+        if let Some(face) = FACE_NAMES.iter().position(|name| *name == letter) {
+            faces[facelet] = face;
+        }
+    }
+    faces
+}
+
+// Which corner is at a position and how it is twisted, None if its stickers
+// are no corner of the cube
+pub(crate) fn corner_at(faces: &[usize; 54], position: usize) -> Option<(u8, u8)> {
+    let facelets = CORNER_FACELETS[position];
+    let orientation = facelets.iter().position(|facelet| faces[*facelet] == 0 || faces[*facelet] == 3)?;
+    let face_1 = faces[facelets[(orientation + 1) % 3]];
+    let face_2 = faces[facelets[(orientation + 2) % 3]];
+    let corner = CORNER_FACES.iter().position(|corner_faces| corner_faces[1] == face_1 && corner_faces[2] == face_2)?;
+    if CORNER_FACES[corner][0] != faces[facelets[orientation]] {
+        return None;
+    }
+    Some((corner as u8, orientation as u8))
+}
+
+pub(crate) fn edge_at(faces: &[usize; 54], position: usize) -> Option<(u8, u8)> {
+    let face_0 = faces[EDGE_FACELETS[position][0]];
+    let face_1 = faces[EDGE_FACELETS[position][1]];
+    EDGE_FACES.iter().enumerate().find_map(|(edge, edge_faces)| {
+        if edge_faces[0] == face_0 && edge_faces[1] == face_1 {
+            Some((edge as u8, 0))
+        } else if edge_faces[0] == face_1 && edge_faces[1] == face_0 {
+            Some((edge as u8, 1))
+        } else {
+            None
+        }
+    })
+}
+
+
+impl CubieCube {
+    pub fn new() -> CubieCube {
+        CubieCube {
+            corner_permutation: [0, 1, 2, 3, 4, 5, 6, 7],
+            corner_orientation: [0; 8],
+            edge_permutation: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            edge_orientation: [0; 12],
+        }
+    }
+
+    // Reads the pieces relative to the centers, so a cube turned with x, y or z
+    // gives the same cubies as before the rotation. None if some stickers do
+    // not form a real piece.
+    pub fn from_cube(cube: &Cube) -> Option<CubieCube> {
+        let faces = facelet_faces(cube);
+        let mut cubie_cube = CubieCube::new();
+        for position in 0..8 {
+            let (corner, orientation) = corner_at(&faces, position)?;
+            cubie_cube.corner_permutation[position] = corner;
+            cubie_cube.corner_orientation[position] = orientation;
+        }
+        for position in 0..12 {
+            let (edge, orientation) = edge_at(&faces, position)?;
+            cubie_cube.edge_permutation[position] = edge;
+            cubie_cube.edge_orientation[position] = orientation;
+        }
+        Some(cubie_cube)
+    }
+
+    pub fn to_facelets(&self) -> String {
+        let mut faces = [0; 54];
+        for face in 0..6 {
+            faces[face * 9 + 4] = face;
+        }
+        for (position, facelets) in CORNER_FACELETS.iter().enumerate() {
+            let corner = self.corner_permutation[position] as usize;
+            let orientation = self.corner_orientation[position] as usize;
+            for (i, facelet) in facelets.iter().enumerate() {
+                faces[*facelet] = CORNER_FACES[corner][(i + 3 - orientation) % 3];
+            }
+        }
+        for (position, facelets) in EDGE_FACELETS.iter().enumerate() {
+            let edge = self.edge_permutation[position] as usize;
+            let orientation = self.edge_orientation[position] as usize;
+            for (i, facelet) in facelets.iter().enumerate() {
+                faces[*facelet] = EDGE_FACES[edge][(i + orientation) % 2];
+            }
+        }
+        faces.iter().map(|face| FACE_NAMES[*face]).collect()
+    }
+
+    pub fn to_cube(&self) -> Cube {
+        Cube::from_facelets(&self.to_facelets()).unwrap()
+    }
+
+    // The state reached by doing `self` and then `other` on a solved cube
+    pub fn multiply(&self, other: &CubieCube) -> CubieCube {
+        let mut result = CubieCube::new();
+        for i in 0..8 {
+            let from = other.corner_permutation[i] as usize;
+            result.corner_permutation[i] = self.corner_permutation[from];
+            result.corner_orientation[i] = (self.corner_orientation[from] + other.corner_orientation[i]) % 3;
+        }
+        for i in 0..12 {
+            let from = other.edge_permutation[i] as usize;
+            result.edge_permutation[i] = self.edge_permutation[from];
+            result.edge_orientation[i] = (self.edge_orientation[from] + other.edge_orientation[i]) % 2;
+        }
+        result
+    }
+
+    pub fn inverse(&self) -> CubieCube {
+        let mut result = CubieCube::new();
+        for i in 0..8 {
+            let corner = self.corner_permutation[i] as usize;
+            result.corner_permutation[corner] = i as u8;
+            result.corner_orientation[corner] = (3 - self.corner_orientation[i]) % 3;
+        }
+        for i in 0..12 {
+            let edge = self.edge_permutation[i] as usize;
+            result.edge_permutation[edge] = i as u8;
+            result.edge_orientation[edge] = self.edge_orientation[i];
+        }
+        result
+    }
+
+    // Only outer face turns exist here, the centers never move
+    pub fn rotate(&self, rotation: &Rotation) -> CubieCube {
+        let (face, quarter_turns) = match rotation {
+            Rotation::U => (0, 1),
+            Rotation::R => (1, 1),
+            Rotation::F => (2, 1),
+            Rotation::D => (3, 1),
+            Rotation::L => (4, 1),
+            Rotation::B => (5, 1),
+            Rotation::U2 => (0, 2),
+            Rotation::R2 => (1, 2),
+            Rotation::F2 => (2, 2),
+            Rotation::D2 => (3, 2),
+            Rotation::L2 => (4, 2),
+            Rotation::B2 => (5, 2),
+            Rotation::Ur => (0, 3),
+            Rotation::Rr => (1, 3),
+            Rotation::Fr => (2, 3),
+            Rotation::Dr => (3, 3),
+            Rotation::Lr => (4, 3),
+            Rotation::Br => (5, 3),
+            _ => panic!("{} moves centers and cannot be done on cubies", rotation),
+        };
+        let mut result = self.clone();
+        for _ in 0..quarter_turns {
+            result = result.multiply(&FACE_TURNS[face]);
+        }
+        result
+    }
+
+    pub fn rotate_all(&self, rotations: &[Rotation]) -> CubieCube {
+        let mut cubie_cube = self.clone();
+        for rotation in rotations.iter() {
+            cubie_cube = cubie_cube.rotate(rotation);
+        }
+        cubie_cube
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_algorithm;
+
+    #[test]
+    fn face_turns_match_the_sticker_cube() {
+        let algorithm = parse_algorithm("R U2 F' D L2 B R' U' F2 D' L B'").unwrap();
+        for end in 0..=algorithm.len() {
+            let cube = Cube::new().rotate_all(&algorithm[..end]);
+            let cubie_cube = CubieCube::new().rotate_all(&algorithm[..end]);
+            assert_eq!(CubieCube::from_cube(&cube), Some(cubie_cube.clone()));
+            assert_eq!(cubie_cube.to_cube(), cube);
+        }
+    }
+
+    #[test]
+    fn multiply_and_inverse() {
+        let first = CubieCube::new().rotate_all(&parse_algorithm("R U F'").unwrap());
+        let second = CubieCube::new().rotate_all(&parse_algorithm("D2 L B").unwrap());
+        let both = CubieCube::new().rotate_all(&parse_algorithm("R U F' D2 L B").unwrap());
+        assert_eq!(first.multiply(&second), both);
+        assert_eq!(both.multiply(&both.inverse()), CubieCube::new());
+        assert_eq!(both.inverse().multiply(&both), CubieCube::new());
+        assert_eq!(both.inverse(), CubieCube::new().rotate_all(&parse_algorithm("B' L' D2 F U' R'").unwrap()));
+    }
+
+    #[test]
+    fn reads_pieces_relative_to_the_centers() {
+        for rotations in ["x", "y'", "z2", "x y"] {
+            let cube = Cube::new().rotate_all(&parse_algorithm(rotations).unwrap());
+            assert_eq!(CubieCube::from_cube(&cube), Some(CubieCube::new()));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use crate::cube::Rotation;
+use crate::notation::{ParseError, ParseErrorKind, Parser};
+use crate::transform::inverse;
+
+
+// Algorithm as written by people, before it is expanded into single moves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    Move(Rotation),
+    Sequence(Vec<Expression>),
+    Repeat(Box<Expression>, usize),
+    Inverse(Box<Expression>),
+    // [A, B] is A B A' B'
+    Commutator(Box<Expression>, Box<Expression>),
+    // [A: B] is A B A'
+    Conjugate(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    pub fn expand(&self) -> Vec<Rotation> {
+        match self {
+            Expression::Move(rotation) => vec![rotation.clone()],
+            Expression::Sequence(items) => items.iter().flat_map(|item| item.expand()).collect(),
+            Expression::Repeat(inner, count) => {
+                let rotations = inner.expand();
+                let mut repeated = vec![];
+                for _ in 0..*count {
+                    repeated.extend(rotations.iter().cloned());
+                }
+                repeated
+            },
+            Expression::Inverse(inner) => inverse(&inner.expand()),
+            Expression::Commutator(a, b) => {
+                let a = a.expand();
+                let b = b.expand();
+                let mut rotations = a.clone();
+                rotations.extend(b.iter().cloned());
+                rotations.extend(inverse(&a));
+                rotations.extend(inverse(&b));
+                rotations
+            },
+            Expression::Conjugate(a, b) => {
+                let a = a.expand();
+                let mut rotations = a.clone();
+                rotations.extend(b.expand());
+                rotations.extend(inverse(&a));
+                rotations
+            },
+        }
+    }
+
+    fn fmt_group(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Commutator(_, _) | Expression::Conjugate(_, _) => write!(f, "{}", self),
+            _ => write!(f, "({})", self),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Move(rotation) => write!(f, "{}", rotation),
+            Expression::Sequence(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                Ok(())
+            },
+            Expression::Repeat(inner, count) => {
+                inner.fmt_group(f)?;
+                write!(f, "{}", count)
+            },
+            Expression::Inverse(inner) => match inner.as_ref() {
+                Expression::Repeat(repeated, count) => {
+                    repeated.fmt_group(f)?;
+                    write!(f, "{}'", count)
+                },
+                _ => {
+                    inner.fmt_group(f)?;
+                    write!(f, "'")
+                },
+            },
+            Expression::Commutator(a, b) => write!(f, "[{}, {}]", a, b),
+            Expression::Conjugate(a, b) => write!(f, "[{}: {}]", a, b),
+        }
+    }
+}
+
+
+// Inside brackets a comma separates the two halves of a commutator,
+// everywhere else it is just a separator between moves
+fn parse_sequence(parser: &mut Parser, inside_brackets: bool) -> Result<Expression, ParseError> {
+    let mut items = vec![];
+    loop {
+        parser.skip_separators(!inside_brackets);
+        match parser.peek() {
+            None | Some(')') | Some(']') | Some(',') | Some(':') => break,
+            Some('(') => items.push(parse_group(parser)?),
+            Some('[') => items.push(parse_brackets(parser)?),
+            Some(_) => items.push(Expression::Move(parser.parse_move()?)),
+        }
+    }
+    Ok(Expression::Sequence(items))
+}
+
+fn parse_group(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let open_error = parser.error(1, ParseErrorKind::UnclosedGroup('('));
+    parser.advance();
+    let inner = parse_sequence(parser, false)?;
+    match parser.peek() {
+        Some(')') => parser.advance(),
+        Some(c) => return Err(parser.error(1, ParseErrorKind::UnexpectedCharacter(c))),
+        None => return Err(open_error),
+    }
+    parse_suffix(parser, inner)
+}
+
+fn parse_brackets(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let open_error = parser.error(1, ParseErrorKind::UnclosedGroup('['));
+    parser.advance();
+    let a = parse_sequence(parser, true)?;
+    let is_commutator = match parser.peek() {
+        Some(',') => true,
+        Some(':') => false,
+        Some(']') => return Err(parser.error(1, ParseErrorKind::MissingSeparator)),
+        Some(c) => return Err(parser.error(1, ParseErrorKind::UnexpectedCharacter(c))),
+        None => return Err(open_error),
+    };
+    parser.advance();
+    let b = parse_sequence(parser, true)?;
+    match parser.peek() {
+        Some(']') => parser.advance(),
+        Some(c) => return Err(parser.error(1, ParseErrorKind::UnexpectedCharacter(c))),
+        None => return Err(open_error),
+    }
+    let expression = if is_commutator {
+        Expression::Commutator(Box::new(a), Box::new(b))
+    } else {
+        Expression::Conjugate(Box::new(a), Box::new(b))
+    };
+    parse_suffix(parser, expression)
+}
+
+// A group can be followed by a repeat count, a prime or both, like (R U)3'
+fn parse_suffix(parser: &mut Parser, expression: Expression) -> Result<Expression, ParseError> {
+    let line = parser.line;
+    let column = parser.column;
+    let mut suffix = String::new();
+    while let Some(c) = parser.peek() {
+        if !matches!(c, '\'' | '\u{2019}' | '0'..='9') {
+            break;
+        }
+        suffix.push(c);
+        parser.advance();
+    }
+
+    let invalid = ParseError {
+        line,
+        column,
+        length: suffix.chars().count(),
+        kind: ParseErrorKind::InvalidModifier(suffix.clone()),
+    };
+    let digits = suffix.trim_matches(['\'', '\u{2019}']);
+    let primes = suffix.chars().count() - digits.len();
+    if primes > 1 {
+        return Err(invalid);
+    }
+    let count = if digits.is_empty() {
+        None
+    } else {
+        match digits.parse::<usize>() {
+            Ok(count) => Some(count),
+            Err(_) => return Err(invalid),
+        }
+    };
+
+    let expression = match count {
+        Some(count) => Expression::Repeat(Box::new(expression), count),
+        None => expression,
+    };
+    Ok(if primes == 1 { Expression::Inverse(Box::new(expression)) } else { expression })
+}
+
+
+// Parses algorithms like (R U R' U')3, [R U R', D] or [F: R U R' U']
+pub fn parse_expression(input: &str) -> Result<Expression, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut parser = Parser::new(&chars);
+    let expression = parse_sequence(&mut parser, false)?;
+    match parser.peek() {
+        Some(c) => Err(parser.error(1, ParseErrorKind::UnexpectedCharacter(c))),
+        None => Ok(expression),
+    }
+}
+
+pub fn expand_algorithm(input: &str) -> Result<Vec<Rotation>, ParseError> {
+    Ok(parse_expression(input)?.expand())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{format_algorithm, parse_algorithm};
+
+    fn expanded(input: &str) -> String {
+        format_algorithm(&expand_algorithm(input).unwrap())
+    }
+
+    #[test]
+    fn expands_repetitions_commutators_and_conjugates() {
+        assert_eq!(expanded("(R U)3"), "R U R U R U");
+        assert_eq!(expanded("[R, U]"), "R U R' U'");
+        assert_eq!(expanded("[F: R U R' U']"), "F R U R' U' F'");
+        assert_eq!(expanded("[R U R', D]"), "R U R' D R U' R' D'");
+        assert_eq!(expanded("(R U2)'"), "U2 R'");
+        assert_eq!(expand_algorithm("R U R' U'").unwrap(), parse_algorithm("R U R' U'").unwrap());
+    }
+
+    #[test]
+    fn formats_what_it_parses() {
+        for input in ["[R, U]", "[F: R U R' U']", "(R U R' U')3"] {
+            let expression = parse_expression(input).unwrap();
+            assert_eq!(parse_expression(&expression.to_string()).unwrap(), expression);
+        }
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        let error = parse_expression("R U\nR' (U Q)").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 7));
+        assert_eq!(error.kind, ParseErrorKind::UnknownMove('Q'));
+        let error = parse_expression("[R U R'").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnclosedGroup('['));
+        let error = parse_expression("[R U]").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::MissingSeparator);
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+use crate::cube::Cube;
+
+
+// Faces in the order of the facelet string and the side index each one has here
+pub(crate) const FACE_NAMES: [char; 6] = ['U', 'R', 'F', 'D', 'L', 'B'];
+pub(crate) const FACE_SIDES: [usize; 6] = [5, 3, 4, 0, 1, 2];
+
+// Side and face index of every facelet in the order U1..U9, R1..R9, F1..F9, D1..D9, L1..L9, B1..B9.
+// The sides around the middle are drawn upside down here compared to the facelet layout.
+pub(crate) const FACELETS: [(usize, usize); 54] = [
+    (5, 0), (5, 1), (5, 2), (5, 3), (5, 4), (5, 5), (5, 6), (5, 7), (5, 8),
+    (3, 8), (3, 7), (3, 6), (3, 5), (3, 4), (3, 3), (3, 2), (3, 1), (3, 0),
+    (4, 8), (4, 7), (4, 6), (4, 5), (4, 4), (4, 3), (4, 2), (4, 1), (4, 0),
+    (0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5), (0, 6), (0, 7), (0, 8),
+    (1, 8), (1, 7), (1, 6), (1, 5), (1, 4), (1, 3), (1, 2), (1, 1), (1, 0),
+    (2, 8), (2, 7), (2, 6), (2, 5), (2, 4), (2, 3), (2, 2), (2, 1), (2, 0),
+];
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaceletError {
+    InvalidLength(usize),
+    InvalidCharacter { index: usize, character: char },
+    MisplacedCenter { face: char, found: char },
+}
+
+impl fmt::Display for FaceletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaceletError::InvalidLength(length) => write!(f, "expected 54 facelets, got {}", length),
+            FaceletError::InvalidCharacter { index, character } => {
+                write!(f, "invalid facelet '{}' at position {}, expected one of URFDLB", character, index + 1)
+            },
+            FaceletError::MisplacedCenter { face, found } => write!(f, "center of the {} face is {}", face, found),
+        }
+    }
+}
+
+impl std::error::Error for FaceletError {}
+
+
+impl Cube {
+    // Reads the 54 character facelet string used by Kociemba's solver and most
+    // scanner apps, e.g. UUUUUUUUURRRRRRRRRFFFFFFFFFDDDDDDDDDLLLLLLLLLBBBBBBBBB
+    // for the solved cube. Whitespace is ignored.
+    pub fn from_facelets(facelets: &str) -> Result<Cube, FaceletError> {
+        let letters: Vec<char> = facelets.chars().filter(|c| !c.is_whitespace()).collect();
+        if letters.len() != 54 {
+            return Err(FaceletError::InvalidLength(letters.len()));
+        }
+
+        let mut cube = Cube::new();
+        for (index, letter) in letters.iter().enumerate() {
+            let face = match FACE_NAMES.iter().position(|name| name == letter) {
+                Some(face) => face,
+                None => return Err(FaceletError::InvalidCharacter { index, character: *letter }),
+            };
+            if index % 9 == 4 && face != index / 9 {
+                return Err(FaceletError::MisplacedCenter { face: FACE_NAMES[index / 9], found: *letter });
+            }
+            let (side, face_index) = FACELETS[index];
+            cube.set_at(side, face_index, FACE_SIDES[face] as u8);
+        }
+        Ok(cube)
+    }
+
+    // Letters are given by the centers, so a whole cube rotation of the solved
+    // cube still reads as solved. Colors no center has are written as '?'.
+    pub fn to_facelets(&self) -> String {
+        FACELETS.iter().map(|(side, face_index)| {
+            let color = self.get_at(*side, *face_index);
+            match FACE_SIDES.iter().position(|center_side| self.get_at(*center_side, 4) == color) {
+                Some(face) => FACE_NAMES[face],
+                None => '?',
+            }
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Rotation;
+
+    const SOLVED: &str = "UUUUUUUUURRRRRRRRRFFFFFFFFFDDDDDDDDDLLLLLLLLLBBBBBBBBB";
+
+    #[test]
+    fn reads_and_writes_facelet_strings() {
+        assert_eq!(Cube::from_facelets(SOLVED), Ok(Cube::new()));
+        assert_eq!(Cube::new().to_facelets(), SOLVED);
+
+        let cube = Cube::new().rotate(&Rotation::R);
+        assert_eq!(cube.to_facelets(), "UUFUUFUUFRRRRRRRRRFFDFFDFFDDDBDDBDDBLLLLLLLLLUBBUBBUBB");
+        assert_eq!(Cube::from_facelets(&cube.to_facelets()), Ok(cube));
+        assert_eq!(Cube::new().rotate(&Rotation::X).to_facelets(), SOLVED);
+    }
+
+    #[test]
+    fn rejects_bad_facelet_strings() {
+        assert_eq!(Cube::from_facelets("UUU"), Err(FaceletError::InvalidLength(3)));
+        let bad_character = SOLVED.replacen('R', "X", 1);
+        assert_eq!(
+            Cube::from_facelets(&bad_character),
+            Err(FaceletError::InvalidCharacter { index: 9, character: 'X' }),
+        );
+        let mut swapped_center: Vec<char> = SOLVED.chars().collect();
+        swapped_center.swap(4, 13);
+        let swapped_center: String = swapped_center.into_iter().collect();
+        assert_eq!(
+            Cube::from_facelets(&swapped_center),
+            Err(FaceletError::MisplacedCenter { face: 'U', found: 'R' }),
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::cube::{two_way_search, Cube, Metric, Rotation};
+use crate::cubie::{CORNER_FACELETS, EDGE_FACELETS};
+use crate::facelets::FACELETS;
+use crate::solver::{SolveError, SolverConfig};
+
+
+// Positions (side * 9 + face) of the stickers of every corner and edge
+fn piece_positions() -> Vec<Vec<usize>> {
+    let corners = CORNER_FACELETS.iter().map(|facelets| facelets.to_vec());
+    let edges = EDGE_FACELETS.iter().map(|facelets| facelets.to_vec());
+    corners.chain(edges).map(|facelets| {
+        facelets.iter().map(|facelet| {
+            let (side, face) = FACELETS[*facelet];
+            side * 9 + face
+        }).collect()
+    }).collect()
+}
+
+// The colors of a piece as bits, which tells the pieces apart
+fn color_bits(colors: impl Iterator<Item = u8>) -> usize {
+    colors.fold(0, |bits, color| bits | 1 << color)
+}
+
+
+// A goal where only some pieces or stickers matter, e.g. only the cross. Every
+// sticker of the solved cube is given a color of the pattern and a cube
+// matches when it looks like the solved cube with its stickers recolored the
+// same way. Stickers of the same pattern color are interchangeable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoalPattern {
+    // Pattern color of each sticker by the color bits of its piece and its color
+    colors: Vec<[u8; 6]>,
+}
+
+impl GoalPattern {
+    // The color of the sticker at each position of the solved cube, at most 6
+    // colors numbered from 0. Centers never move and keep their colors.
+    pub fn from_colors(colors: &[u8; 54]) -> GoalPattern {
+        assert!(colors.iter().all(|color| *color < 6), "pattern colors have to be below 6");
+        let mut pattern_colors = vec![[0; 6]; 64];
+        for positions in piece_positions() {
+            let bits = color_bits(positions.iter().map(|position| (position / 9) as u8));
+            for position in positions {
+                pattern_colors[bits][position / 9] = colors[position];
+            }
+        }
+        GoalPattern { colors: pattern_colors }
+    }
+
+    // Every sticker matters, the same as solving to the solved cube
+    pub fn solved() -> GoalPattern {
+        let mut colors = [0; 54];
+        for (position, color) in colors.iter_mut().enumerate() {
+            *color = (position / 9) as u8;
+        }
+        GoalPattern::from_colors(&colors)
+    }
+
+    // Only the pieces `keep` returns true for matter, it gets the sides of the
+    // stickers of a piece on the solved cube. The stickers of all other pieces
+    // get one color, a kept piece still differs from them by its other colors.
+    pub fn pieces(keep: impl Fn(&[u8]) -> bool) -> GoalPattern {
+        let mut colors = [0; 54];
+        for (position, color) in colors.iter_mut().enumerate() {
+            *color = (position / 9) as u8;
+        }
+        for positions in piece_positions() {
+            let sides: Vec<u8> = positions.iter().map(|position| (position / 9) as u8).collect();
+            if !keep(&sides) {
+                for position in positions {
+                    colors[position] = 5;
+                }
+            }
+        }
+        GoalPattern::from_colors(&colors)
+    }
+
+    // The four edges of the D layer
+    pub fn cross() -> GoalPattern {
+        GoalPattern::pieces(|sides| sides.len() == 2 && sides.contains(&0))
+    }
+
+    // The D and middle layers
+    pub fn first_two_layers() -> GoalPattern {
+        GoalPattern::pieces(|sides| !sides.contains(&5))
+    }
+
+    // Every edge oriented, so it can be solved with U, D, R, L, F2 and B2.
+    // The first sticker of an edge (U or D, F or B for the middle layer) is
+    // where it is judged by.
+    pub fn edge_orientation() -> GoalPattern {
+        let mut colors = [2; 54];
+        for facelets in EDGE_FACELETS.iter() {
+            for (i, facelet) in facelets.iter().enumerate() {
+                let (side, face) = FACELETS[*facelet];
+                colors[side * 9 + face] = i as u8;
+            }
+        }
+        GoalPattern::from_colors(&colors)
+    }
+
+    // The cube with every sticker recolored, the cube has to be valid
+    pub fn apply(&self, cube: &Cube) -> Cube {
+        let mut recolored = cube.clone();
+        for positions in piece_positions() {
+            let bits = color_bits(positions.iter().map(|position| cube.get_at(position / 9, position % 9)));
+            for position in positions {
+                let color = cube.get_at(position / 9, position % 9);
+                recolored.set_at(position / 9, position % 9, self.colors[bits][color as usize]);
+            }
+        }
+        recolored
+    }
+
+    pub fn matches(&self, cube: &Cube) -> bool {
+        self.apply(cube) == self.apply(&Cube::new())
+    }
+}
+
+
+// The shortest way from the start cube to any cube matching the pattern. The
+// two way search runs on recolored cubes, from the start cube and from the
+// recolored solved cube.
+pub fn solve_to_pattern(
+    start_cube: &Cube,
+    pattern: &GoalPattern,
+    metric: Metric,
+    config: &SolverConfig,
+) -> Result<Vec<Rotation>, SolveError> {
+    start_cube.validate()?;
+    if (0..6).any(|side| start_cube.get_at(side, 4) != side as u8) {
+        return Err(SolveError::Unsolvable)
+    }
+    two_way_search(&pattern.apply(start_cube), &pattern.apply(&Cube::new()), metric, config)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cubie::CubieCube;
+    use crate::notation::parse_algorithm;
+
+    fn scrambled(scramble: &str) -> Cube {
+        Cube::new().rotate_all(&parse_algorithm(scramble).unwrap())
+    }
+
+    // Fewest moves to a cube matching the pattern, trying every sequence
+    fn brute_force_length(start_cube: &Cube, pattern: &GoalPattern) -> usize {
+        let mut cubes = vec![start_cube.clone()];
+        for length in 0.. {
+            if cubes.iter().any(|cube| pattern.matches(cube)) {
+                return length
+            }
+            cubes = cubes.iter().flat_map(|cube| Metric::HalfTurn.rotations().into_iter().map(|rotation| cube.rotate(&rotation))).collect();
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn solves_to_cubes_matching_the_pattern() {
+        let config = SolverConfig::new();
+        for scramble in ["R U F'", "D2 L' B R2 U", "R U2 F' D L2 B R' U F2 D' L B2"] {
+            let start_cube = scrambled(scramble);
+            for pattern in [GoalPattern::cross(), GoalPattern::edge_orientation()] {
+                let solution = solve_to_pattern(&start_cube, &pattern, Metric::HalfTurn, &config).unwrap();
+                assert!(pattern.matches(&start_cube.rotate_all(&solution)));
+            }
+        }
+        let start_cube = scrambled("D2 L' B R2");
+        let solution = solve_to_pattern(&start_cube, &GoalPattern::solved(), Metric::HalfTurn, &config).unwrap();
+        assert_eq!(solution.len(), 4);
+        let cross = solve_to_pattern(&start_cube, &GoalPattern::cross(), Metric::HalfTurn, &config).unwrap();
+        assert_eq!(cross.len(), brute_force_length(&start_cube, &GoalPattern::cross()));
+    }
+
+    #[test]
+    fn only_the_pattern_matters() {
+        assert!(GoalPattern::cross().matches(&scrambled("U R U R' U'")));
+        assert!(!GoalPattern::cross().matches(&scrambled("R")));
+        assert!(GoalPattern::first_two_layers().matches(&scrambled("R U R' U'").rotate_all(&parse_algorithm("U R U' R'").unwrap())));
+        assert!(!GoalPattern::solved().matches(&scrambled("U")));
+
+        let oriented = scrambled("R U L D2 F2 B2");
+        assert!(GoalPattern::edge_orientation().matches(&oriented));
+        assert_eq!(CubieCube::from_cube(&oriented).unwrap().flip(), 0);
+        assert!(!GoalPattern::edge_orientation().matches(&scrambled("F")));
+
+        let solution = solve_to_pattern(&scrambled("U2 R U'"), &GoalPattern::cross(), Metric::HalfTurn, &SolverConfig::new());
+        assert_eq!(solution.unwrap().len(), 1);
+    }
+}
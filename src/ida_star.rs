@@ -0,0 +1,173 @@
+use crate::cube::{Cube, Metric, Rotation};
+use crate::coordinates::is_odd_permutation;
+use crate::pattern_database::{pattern_databases, PatternDatabases};
+use crate::simplify::decompose;
+use crate::solver::{relative_cubie, SearchLimits, SolveError, SolverConfig};
+
+
+struct Search<'a> {
+    databases: &'a PatternDatabases,
+    limits: SearchLimits<'a>,
+    // Cubes looked at in all searches so far
+    nodes: usize,
+    // Axis and layer (0 or 1 for the two faces) of every move
+    layers: Vec<(usize, usize)>,
+    path: Vec<usize>,
+    next_bound: u8,
+}
+
+impl Search<'_> {
+    // Skips moves that undo or repeat the last move and orders moves on
+    // opposite faces, since those commute
+    fn allowed(&self, rotation: usize) -> bool {
+        let last = match self.path.last() {
+            Some(last) => *last,
+            None => return true,
+        };
+        let (axis, layer) = self.layers[rotation];
+        let (last_axis, last_layer) = self.layers[last];
+        if axis != last_axis {
+            return true;
+        }
+        if layer != last_layer {
+            return layer > last_layer;
+        }
+        // Two quarter turns are how a half turn is written in the quarter turn metric
+        let twice_before = self.path.len() >= 2 && self.layers[self.path[self.path.len() - 2]] == self.layers[rotation];
+        self.databases.metric == Metric::QuarterTurn && rotation == last && !twice_before
+    }
+
+    fn search(&mut self, indices: [usize; 3], bound: u8) -> Result<bool, SolveError> {
+        // Only the path is stored, so there is no memory to count
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) {
+            self.limits.check(self.nodes, 0)?;
+        }
+        let distance = self.databases.estimate(indices);
+        if distance == 0 {
+            return Ok(true);
+        }
+        let estimate = self.path.len() as u8 + distance;
+        if estimate > bound {
+            self.next_bound = self.next_bound.min(estimate);
+            return Ok(false);
+        }
+        for rotation in 0..self.layers.len() {
+            if !self.allowed(rotation) {
+                continue;
+            }
+            self.path.push(rotation);
+            if self.search(self.databases.rotate_indices(indices, rotation), bound)? {
+                return Ok(true);
+            }
+            self.path.pop();
+        }
+        Ok(false)
+    }
+}
+
+
+// Iterative deepening A*: depth first searches with a growing bound on moves
+// made plus the pattern database estimate. Only uses memory for the databases
+// and always finds a shortest solution, though random states can take long.
+// The config limits the depth, the cubes looked at and the time, the memory
+// budget and the moves are not used.
+pub fn solve_cube_ida_star(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    metric: Metric,
+    config: &SolverConfig,
+) -> Result<Vec<Rotation>, SolveError> {
+    solve_cube_ida_star_with_databases(start_cube, end_cube, &pattern_databases(metric).0, config)
+}
+
+pub fn solve_cube_ida_star_with_databases(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    databases: &PatternDatabases,
+    config: &SolverConfig,
+) -> Result<Vec<Rotation>, SolveError> {
+    let cube = relative_cubie(start_cube, end_cube)?;
+
+    let layers = databases.rotations.iter().map(|rotation| {
+        let (axis, layer, _) = decompose(rotation);
+        (axis, layer)
+    }).collect();
+    let limits = SearchLimits::new(config);
+    let mut search = Search { databases, limits, nodes: 0, layers, path: vec![], next_bound: 0 };
+
+    // Every quarter turn swaps corners, so the corner permutation tells
+    // whether a solution has an odd or even number of quarter turns
+    let parity = is_odd_permutation(&cube.corner_permutation) as u8;
+    let indices = databases.indices(&cube);
+    let mut bound = databases.estimate(indices);
+    loop {
+        if databases.metric == Metric::QuarterTurn && bound % 2 != parity {
+            bound += 1;
+        }
+        if bound as usize > config.max_depth {
+            return Err(SolveError::DepthLimit { depth: config.max_depth });
+        }
+        if config.verbose {
+            println!("Searching {} moves", bound);
+        }
+        search.next_bound = u8::MAX;
+        if search.search(indices, bound)? {
+            return Ok(search.path.iter().map(|rotation| databases.rotations[*rotation].clone()).collect());
+        }
+        bound = search.next_bound;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::cube::solve_cube_two_way_breath_first;
+    use crate::cubie::CubieCube;
+    use crate::notation::parse_algorithm;
+    use crate::pattern_database::{NibbleTable, CORNER_STATES, EDGE_GROUP_STATES};
+
+    // The real databases take too long to build for a test. Tables with a
+    // distance of 1 everywhere but the solved cube still never overestimate.
+    fn databases(metric: Metric) -> PatternDatabases {
+        let mut databases = PatternDatabases::with_move_tables(metric);
+        let solved = databases.indices(&CubieCube::new());
+        databases.corners = NibbleTable { data: vec![0x11; CORNER_STATES.div_ceil(2)] };
+        databases.edges = NibbleTable { data: vec![0x11; EDGE_GROUP_STATES.div_ceil(2)] };
+        databases.corners.set(solved[0], 0);
+        databases.edges.set(solved[1], 0);
+        databases
+    }
+
+    #[test]
+    fn finds_solutions_as_short_as_breadth_first_search() {
+        let config = SolverConfig::new();
+        for metric in [Metric::HalfTurn, Metric::QuarterTurn] {
+            let databases = databases(metric);
+            for scramble in ["R U F", "R2 D' B L'", "U F2 R'"] {
+                let start_cube = Cube::new().rotate_all(&parse_algorithm(scramble).unwrap());
+                let solution = solve_cube_ida_star_with_databases(&start_cube, &Cube::new(), &databases, &config).unwrap();
+                assert_eq!(start_cube.rotate_all(&solution), Cube::new());
+                let shortest = solve_cube_two_way_breath_first(&start_cube, &Cube::new(), metric, &config).unwrap();
+                assert_eq!(solution.len(), shortest.len());
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_the_limits() {
+        let databases = databases(Metric::HalfTurn);
+        let start_cube = Cube::new().rotate_all(&parse_algorithm("R U F D L B").unwrap());
+        let solve = |config: &SolverConfig| solve_cube_ida_star_with_databases(&start_cube, &Cube::new(), &databases, config);
+        assert_eq!(solve(&SolverConfig::new().with_max_depth(3)), Err(SolveError::DepthLimit { depth: 3 }));
+        assert_eq!(solve(&SolverConfig::new().with_max_nodes(5000)), Err(SolveError::NodeLimit { nodes: 5000 }));
+        assert!(matches!(solve(&SolverConfig::new().with_timeout(Duration::ZERO)), Err(SolveError::Timeout { .. })));
+        let held_differently = Cube::new().rotate(&Rotation::X);
+        assert_eq!(
+            solve_cube_ida_star_with_databases(&held_differently, &Cube::new(), &databases, &SolverConfig::new()),
+            Err(SolveError::Unsolvable),
+        );
+    }
+}
@@ -1,28 +1,146 @@
 pub mod printing;
 pub mod cube;
+pub mod notation;
+pub mod expression;
+pub mod simplify;
+pub mod transform;
+pub mod metrics;
+pub mod facelets;
+pub mod cubie;
+pub mod validation;
+pub mod coordinates;
+pub mod pattern_database;
+pub mod ida_star;
+pub mod two_phase;
+pub mod thistlethwaite;
+pub mod table_file;
+pub mod symmetry;
+pub mod solver;
+pub mod optimal_solutions;
+pub mod near_optimal;
+pub mod subgroup;
+pub mod goal_pattern;
+pub mod cfop;
 
 use printing::*;
 use cube::*;
+use notation::*;
+use expression::*;
+use pattern_database::{PatternDatabases, PATTERN_DATABASE_KIND};
+use two_phase::{solve_cube_two_phase_with_tables, two_phase_tables, TwoPhaseTables, TWO_PHASE_KIND};
+use thistlethwaite::{ThistlethwaiteTables, THISTLETHWAITE_KIND};
+use solver::SolverConfig;
+use cfop::solve_cube_cfop;
+use table_file::{table_directory, table_file_name, TableError, TableSource};
+use std::path::{Path, PathBuf};
 
 
 fn main() {
     let solved_cube= Cube::new();
 
-    let start_cube = Cube::new_shuffled(15);
-    // let mut start_cube = Cube::new();
-    // start_cube.set_at(2, 3, 1);
-    // start_cube.set_at(2, 5, 3);
-    // start_cube.set_at(1, 5, 2);
-    // start_cube.set_at(3, 3, 2);
+    // A scramble can be given as arguments, e.g. cargo run -- "R U R' U'",
+    // or the state of a real cube as a 54 character facelet string
+    let mut scramble: Vec<String> = std::env::args().skip(1).collect();
+    if scramble.first().is_some_and(|command| command == "build-tables") {
+        build_tables(scramble.get(1));
+        return;
+    }
+    // cargo run -- cfop "R U R' U'" solves step by step the way a person would
+    let cfop = scramble.first().is_some_and(|command| command == "cfop");
+    // cargo run -- two-phase "R U R' U'" solves in at most 21 moves with the
+    // two-phase tables, loaded from the table directory if they were saved
+    let two_phase = scramble.first().is_some_and(|command| command == "two-phase");
+    if cfop || two_phase {
+        scramble.remove(0);
+    }
+    let input = scramble.join(" ");
+    let start_cube = if scramble.is_empty() {
+        let rotations = random_scramble(15);
+        println!("SCRAMBLE: {}", format_algorithm(&rotations));
+        solved_cube.rotate_all(&rotations)
+    } else if let Ok(cube) = Cube::from_facelets(&input) {
+        cube
+    } else {
+        match expand_algorithm(&input) {
+            Ok(rotations) => solved_cube.rotate_all(&rotations),
+            Err(error) => {
+                println!("INVALID SCRAMBLE: {}", error);
+                return;
+            }
+        }
+    };
 
-    let solution = solve_cube_two_way_breath_first(&start_cube, &solved_cube);
+    if let Err(error) = start_cube.validate() {
+        println!("INVALID CUBE: {}", error);
+        return;
+    }
+
+    if cfop {
+        match solve_cube_cfop(&start_cube, &SolverConfig::new()) {
+            Ok(steps) => {
+                let (names, phases): (Vec<&str>, Vec<Vec<Rotation>>) = steps.iter()
+                    .map(|(name, rotations)| (name.as_str(), rotations.clone()))
+                    .unzip();
+                print_phased_solution(&start_cube, &phases, &names);
+            },
+            Err(error) => {
+                println!("NO SOLUTION FOUND: {}", error);
+            }
+        }
+        return;
+    }
+
+    if two_phase {
+        let (tables, source) = two_phase_tables(Metric::HalfTurn);
+        if let TableSource::Rebuilt(path, error) = source {
+            println!("NOT USING {}: {}", path.display(), error);
+        }
+        let config = SolverConfig::new().with_max_depth(21);
+        match solve_cube_two_phase_with_tables(&start_cube, &solved_cube, tables, &config) {
+            Ok(solution) => {
+                print_solution(&start_cube, &solution, &[]);
+            },
+            Err(error) => {
+                println!("NO SOLUTION FOUND: {}", error);
+            }
+        }
+        return;
+    }
+
+    let config = SolverConfig::new().with_verbose(true);
+    let solution = solve_cube_two_way_breath_first(&start_cube, &solved_cube, Metric::QuarterTurn, &config);
 
     match solution {
-        Some(solution) => {
-            print_solution(&start_cube, &solution);
+        Ok(solution) => {
+            print_solution(&start_cube, &solution, &[]);
         },
-        None => {
-            println!("NO SOLUTION FOUND");
+        Err(error) => {
+            println!("NO SOLUTION FOUND: {}", error);
+        }
+    }
+}
+
+
+// Builds every table the solvers use and saves it, so later runs only load
+// them, e.g. cargo run --release -- build-tables [directory]
+fn build_tables(directory: Option<&String>) {
+    let directory = directory.map(PathBuf::from).unwrap_or_else(table_directory);
+    let save = |kind: &str, metric: Metric, save: &dyn Fn(&Path) -> Result<(), TableError>| {
+        let path = directory.join(table_file_name(kind, metric));
+        match save(&path) {
+            Ok(()) => println!("SAVED {}", path.display()),
+            Err(error) => println!("COULD NOT SAVE {}: {}", path.display(), error),
         }
+    };
+    for metric in [Metric::QuarterTurn, Metric::HalfTurn] {
+        println!("BUILDING {} {:?}", PATTERN_DATABASE_KIND, metric);
+        let databases = PatternDatabases::new(metric);
+        save(PATTERN_DATABASE_KIND, metric, &|path| databases.save(path));
+        println!("BUILDING {} {:?}", TWO_PHASE_KIND, metric);
+        let tables = TwoPhaseTables::new(metric);
+        save(TWO_PHASE_KIND, metric, &|path| tables.save(path));
     }
+    println!("BUILDING {}", THISTLETHWAITE_KIND);
+    let tables = ThistlethwaiteTables::new();
+    save(THISTLETHWAITE_KIND, Metric::HalfTurn, &|path| tables.save(path));
 }
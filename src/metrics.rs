@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use crate::cube::Rotation;
+use crate::simplify::decompose;
+
+
+// Weight per move for custom metrics, moves without a weight use the default
+#[derive(Debug, Clone)]
+pub struct MoveWeights {
+    weights: HashMap<Rotation, f64>,
+    default: f64,
+}
+
+impl MoveWeights {
+    pub fn new(default: f64) -> MoveWeights {
+        MoveWeights { weights: HashMap::new(), default }
+    }
+
+    pub fn with(mut self, rotation: Rotation, weight: f64) -> MoveWeights {
+        self.weights.insert(rotation, weight);
+        self
+    }
+
+    pub fn weight(&self, rotation: &Rotation) -> f64 {
+        *self.weights.get(rotation).unwrap_or(&self.default)
+    }
+}
+
+
+// Length of an algorithm in the usual metrics:
+// HTM counts every outer layer turn as one move, slices as two and rotations as none,
+// QTM is like HTM but half turns count twice,
+// STM counts every face, slice or wide turn as one move,
+// ETM counts everything including whole cube rotations.
+pub trait MoveCount {
+    fn htm(&self) -> usize;
+    fn qtm(&self) -> usize;
+    fn stm(&self) -> usize;
+    fn etm(&self) -> usize;
+    fn weighted(&self, weights: &MoveWeights) -> f64;
+}
+
+impl MoveCount for [Rotation] {
+    fn htm(&self) -> usize {
+        self.iter().map(|rotation| match decompose(rotation) {
+            (_, 2, _) => 2,
+            (_, 5, _) => 0,
+            _ => 1,
+        }).sum()
+    }
+
+    fn qtm(&self) -> usize {
+        self.iter().map(|rotation| {
+            let (_, layer, quarter_turns) = decompose(rotation);
+            let quarter_turns = if quarter_turns == 2 { 2 } else { 1 };
+            match layer {
+                2 => 2 * quarter_turns,
+                5 => 0,
+                _ => quarter_turns,
+            }
+        }).sum()
+    }
+
+    fn stm(&self) -> usize {
+        self.iter().filter(|rotation| decompose(rotation).1 != 5).count()
+    }
+
+    fn etm(&self) -> usize {
+        self.len()
+    }
+
+    fn weighted(&self, weights: &MoveWeights) -> f64 {
+        self.iter().map(|rotation| weights.weight(rotation)).sum()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_algorithm;
+
+    #[test]
+    fn counts_in_every_metric() {
+        let algorithm = parse_algorithm("R U2 M' x Rw").unwrap();
+        assert_eq!(algorithm.htm(), 5);
+        assert_eq!(algorithm.qtm(), 6);
+        assert_eq!(algorithm.stm(), 4);
+        assert_eq!(algorithm.etm(), 5);
+
+        let slices = parse_algorithm("M2 E S'").unwrap();
+        assert_eq!(slices.htm(), 6);
+        assert_eq!(slices.qtm(), 8);
+        assert_eq!(slices.stm(), 3);
+    }
+
+    #[test]
+    fn weights_moves() {
+        let weights = MoveWeights::new(1.0).with(Rotation::R, 0.5).with(Rotation::B2, 3.0);
+        assert_eq!(parse_algorithm("R R' B2").unwrap().weighted(&weights), 4.5);
+        assert_eq!(Vec::<Rotation>::new().weighted(&weights), 0.0);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::cube::*;
+use crate::simplify::decompose;
+use crate::solver::{SearchLimits, SolveError, SolverConfig};
+use crate::symmetry::Symmetry;
+
+
+// A cube on the way of the depth first search and the next move to try
+struct Frame {
+    cube: Cube,
+    last_turn: LastTurn,
+    next: usize,
+}
+
+// Solutions of the shortest length first, then one move longer and so on.
+// Moves are only written in one order where the order does not matter, so
+// U D is returned but not D U, and a solution never passes the end cube.
+pub struct NearOptimalSolutions {
+    rotations: Vec<Rotation>,
+    turns: Vec<(usize, usize, u8)>,
+    metric: Metric,
+    symmetries: Vec<&'static Symmetry>,
+    start_cube: Cube,
+    end_cube: Cube,
+    // Distances to the end cube up to `distances_depth` moves away
+    end_keys: Visited,
+    distances_depth: u8,
+    pub optimal_length: usize,
+    max_length: usize,
+    // Length of the solutions searched for now and next
+    length: usize,
+    next_length: usize,
+    remaining: usize,
+    stack: Vec<Frame>,
+    path: Vec<Rotation>,
+}
+
+impl NearOptimalSolutions {
+    // Exact up to `distances_depth`, a lower bound beyond
+    fn distance(&self, cube: &Cube) -> usize {
+        match self.end_keys.get(&cube.symmetry_key(&self.symmetries)) {
+            Some((_, distance)) => *distance as usize,
+            None => self.distances_depth as usize + 1,
+        }
+    }
+}
+
+impl Iterator for NearOptimalSolutions {
+    type Item = Vec<Rotation>;
+
+    fn next(&mut self) -> Option<Vec<Rotation>> {
+        while self.remaining > 0 {
+            let Some(frame) = self.stack.last_mut() else {
+                if self.next_length > self.max_length {
+                    return None
+                }
+                self.length = self.next_length;
+                // Quarter turns always change the parity of the cube
+                self.next_length += if self.metric == Metric::QuarterTurn { 2 } else { 1 };
+                if self.length == 0 {
+                    self.remaining -= 1;
+                    return Some(vec![])
+                }
+                self.stack.push(Frame { cube: self.start_cube.clone(), last_turn: None, next: 0 });
+                continue;
+            };
+            if frame.next == self.rotations.len() {
+                self.stack.pop();
+                self.path.pop();
+                continue;
+            }
+            let i = frame.next;
+            frame.next += 1;
+            if is_redundant(frame.last_turn, self.turns[i], self.metric, true) {
+                continue;
+            }
+            let cube = frame.cube.rotate(&self.rotations[i]);
+            let last_turn = next_last_turn(frame.last_turn, self.turns[i]);
+            let togo = self.length - self.stack.len();
+            if self.distance(&cube) > togo {
+                continue;
+            }
+            if togo == 0 {
+                // The distance is 0, so this is the end cube
+                self.remaining -= 1;
+                let mut solution = self.path.clone();
+                solution.push(self.rotations[i].clone());
+                return Some(solution)
+            }
+            if cube == self.end_cube {
+                continue;
+            }
+            self.path.push(self.rotations[i].clone());
+            self.stack.push(Frame { cube, last_turn, next: 0 });
+        }
+        None
+    }
+}
+
+
+// All solutions at most `extra_moves` longer than the shortest ones, at most
+// `max_solutions` of them. The config limits finding the shortest length and
+// the table of distances to the end cube the search is guided by.
+pub fn near_optimal_solutions(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    metric: Metric,
+    extra_moves: usize,
+    max_solutions: usize,
+    config: &SolverConfig,
+) -> Result<NearOptimalSolutions, SolveError> {
+    let optimal_length = solve_cube_two_way_breath_first(start_cube, end_cube, metric, config)?.len();
+    let max_length = optimal_length + extra_moves;
+    let distances_depth = max_length.div_ceil(2);
+    let end_keys = distances_to_end(end_cube, metric, distances_depth, &SearchLimits::new(config))?;
+    let rotations = config.rotations(metric)?;
+    Ok(NearOptimalSolutions {
+        turns: rotations.iter().map(decompose).collect(),
+        symmetries: search_symmetries(end_cube, &rotations),
+        rotations,
+        metric,
+        start_cube: start_cube.clone(),
+        end_cube: end_cube.clone(),
+        end_keys,
+        distances_depth: distances_depth as u8,
+        optimal_length,
+        max_length,
+        length: optimal_length,
+        next_length: optimal_length,
+        remaining: max_solutions,
+        stack: vec![],
+        path: vec![],
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_algorithm;
+
+    // Every sequence of at most `max_length` moves that solves the cube, with
+    // moves written the way the search writes them and not passing the end
+    fn brute_force(start_cube: &Cube, metric: Metric, max_length: usize) -> Vec<Vec<Rotation>> {
+        let mut solutions = vec![];
+        let mut sequences: Vec<(Cube, LastTurn, Vec<Rotation>)> = vec![(start_cube.clone(), None, vec![])];
+        for _ in 0..max_length {
+            let mut next = vec![];
+            for (cube, last_turn, sequence) in sequences.iter() {
+                if *cube == Cube::new() {
+                    continue;
+                }
+                for rotation in metric.rotations() {
+                    let turn = decompose(&rotation);
+                    if is_redundant(*last_turn, turn, metric, true) {
+                        continue;
+                    }
+                    let mut sequence = sequence.clone();
+                    sequence.push(rotation.clone());
+                    let cube = cube.rotate(&rotation);
+                    if cube == Cube::new() {
+                        solutions.push(sequence.clone());
+                    }
+                    next.push((cube, next_last_turn(*last_turn, turn), sequence));
+                }
+            }
+            sequences = next;
+        }
+        solutions
+    }
+
+    #[test]
+    fn finds_every_solution_shortest_first() {
+        for (scramble, metric) in [("R U F'", Metric::HalfTurn), ("R U2", Metric::QuarterTurn)] {
+            let start_cube = Cube::new().rotate_all(&parse_algorithm(scramble).unwrap());
+            let solutions: Vec<Vec<Rotation>> =
+                near_optimal_solutions(&start_cube, &Cube::new(), metric, 2, 10000, &SolverConfig::new()).unwrap().collect();
+            assert!(solutions.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+            let optimal_length = solutions[0].len();
+            let mut expected = brute_force(&start_cube, metric, optimal_length + 2);
+            let mut found = solutions.clone();
+            expected.sort_by_key(|solution| format!("{:?}", solution));
+            found.sort_by_key(|solution| format!("{:?}", solution));
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_maximum_number_of_solutions() {
+        let start_cube = Cube::new().rotate_all(&parse_algorithm("R U F'").unwrap());
+        let solutions = |max_solutions| {
+            near_optimal_solutions(&start_cube, &Cube::new(), Metric::HalfTurn, 4, max_solutions, &SolverConfig::new()).unwrap()
+        };
+        assert_eq!(solutions(10).optimal_length, 3);
+        assert_eq!(solutions(10).count(), 2);
+        assert_eq!(solutions(1).count(), 1);
+        let solved = near_optimal_solutions(&Cube::new(), &Cube::new(), Metric::HalfTurn, 0, 5, &SolverConfig::new()).unwrap();
+        assert_eq!(solved.collect::<Vec<_>>(), vec![vec![]]);
+    }
+}
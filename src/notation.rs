@@ -0,0 +1,304 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::cube::Rotation;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnknownMove(char),
+    InvalidModifier(String),
+    UnsupportedLayers(String),
+    TrailingInput(String),
+    Empty,
+    UnclosedGroup(char),
+    UnexpectedCharacter(char),
+    MissingSeparator,
+}
+
+// Position is 1-based and counted in characters, like editors show it.
+// The span covers `length` characters starting at the position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            ParseErrorKind::UnknownMove(c) => write!(f, "unknown move '{}'", c),
+            ParseErrorKind::InvalidModifier(m) => write!(f, "invalid move modifier '{}'", m),
+            ParseErrorKind::UnsupportedLayers(n) => write!(f, "unsupported layer prefix '{}', a 3x3x3 only has 2-layer wide moves like 2Rw", n),
+            ParseErrorKind::TrailingInput(rest) => write!(f, "unexpected '{}' after move", rest),
+            ParseErrorKind::Empty => write!(f, "expected a move"),
+            ParseErrorKind::UnclosedGroup(c) => write!(f, "'{}' is never closed", c),
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected '{}'", c),
+            ParseErrorKind::MissingSeparator => write!(f, "expected ',' for a commutator or ':' for a conjugate"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Rotation::U => "U",
+            Rotation::D => "D",
+            Rotation::R => "R",
+            Rotation::L => "L",
+            Rotation::F => "F",
+            Rotation::B => "B",
+            Rotation::Ur => "U'",
+            Rotation::Dr => "D'",
+            Rotation::Rr => "R'",
+            Rotation::Lr => "L'",
+            Rotation::Fr => "F'",
+            Rotation::Br => "B'",
+            Rotation::U2 => "U2",
+            Rotation::D2 => "D2",
+            Rotation::R2 => "R2",
+            Rotation::L2 => "L2",
+            Rotation::F2 => "F2",
+            Rotation::B2 => "B2",
+            Rotation::M => "M",
+            Rotation::E => "E",
+            Rotation::S => "S",
+            Rotation::Mr => "M'",
+            Rotation::Er => "E'",
+            Rotation::Sr => "S'",
+            Rotation::M2 => "M2",
+            Rotation::E2 => "E2",
+            Rotation::S2 => "S2",
+            Rotation::X => "x",
+            Rotation::Y => "y",
+            Rotation::Z => "z",
+            Rotation::Xr => "x'",
+            Rotation::Yr => "y'",
+            Rotation::Zr => "z'",
+            Rotation::X2 => "x2",
+            Rotation::Y2 => "y2",
+            Rotation::Z2 => "z2",
+            Rotation::Uw => "Uw",
+            Rotation::Dw => "Dw",
+            Rotation::Rw => "Rw",
+            Rotation::Lw => "Lw",
+            Rotation::Fw => "Fw",
+            Rotation::Bw => "Bw",
+            Rotation::Uwr => "Uw'",
+            Rotation::Dwr => "Dw'",
+            Rotation::Rwr => "Rw'",
+            Rotation::Lwr => "Lw'",
+            Rotation::Fwr => "Fw'",
+            Rotation::Bwr => "Bw'",
+            Rotation::Uw2 => "Uw2",
+            Rotation::Dw2 => "Dw2",
+            Rotation::Rw2 => "Rw2",
+            Rotation::Lw2 => "Lw2",
+            Rotation::Fw2 => "Fw2",
+            Rotation::Bw2 => "Bw2",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Rotation, ParseError> {
+        let chars: Vec<char> = s.chars().collect();
+        let mut parser = Parser::new(&chars);
+        let rotation = parser.parse_move()?;
+        if parser.index < chars.len() {
+            let rest: String = chars[parser.index..].iter().collect();
+            return Err(parser.error(rest.chars().count(), ParseErrorKind::TrailingInput(rest)));
+        }
+        Ok(rotation)
+    }
+}
+
+
+pub(crate) struct Parser<'a> {
+    chars: &'a [char],
+    index: usize,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(chars: &'a [char]) -> Parser<'a> {
+        Parser { chars, index: 0, line: 1, column: 1 }
+    }
+
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    pub(crate) fn advance(&mut self) {
+        if self.peek() == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        self.index += 1;
+    }
+
+    pub(crate) fn error(&self, length: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError { line: self.line, column: self.column, length, kind }
+    }
+
+    // Commas separate moves in plain move lists but mean something inside brackets
+    pub(crate) fn skip_separators(&mut self, skip_commas: bool) {
+        while let Some(c) = self.peek() {
+            if !(c.is_whitespace() || skip_commas && c == ',') {
+                break;
+            }
+            self.advance();
+        }
+    }
+
+    // Parses one move like U, M', x2, Rw or r. A layer count prefix is only
+    // accepted where it means a wide move on this cube, like 2Rw.
+    pub(crate) fn parse_move(&mut self) -> Result<Rotation, ParseError> {
+        let layers_line = self.line;
+        let layers_column = self.column;
+        let mut layers = String::new();
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            layers.push(c);
+            self.advance();
+        }
+
+        let letter = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error(0, ParseErrorKind::Empty)),
+        };
+        let mut wide = matches!(letter, 'u' | 'd' | 'r' | 'l' | 'f' | 'b');
+        let face = letter.to_ascii_uppercase();
+        let letter_error = self.error(1, ParseErrorKind::UnknownMove(letter));
+        self.advance();
+        if !wide && matches!(face, 'U' | 'D' | 'R' | 'L' | 'F' | 'B') && self.peek() == Some('w') {
+            wide = true;
+            self.advance();
+        }
+        if !(layers.is_empty() || wide && layers == "2") {
+            return Err(ParseError {
+                line: layers_line,
+                column: layers_column,
+                length: layers.chars().count(),
+                kind: ParseErrorKind::UnsupportedLayers(layers),
+            });
+        }
+
+        let (clockwise, counter_clockwise, half) = match (face, wide) {
+            ('U', false) => (Rotation::U, Rotation::Ur, Rotation::U2),
+            ('D', false) => (Rotation::D, Rotation::Dr, Rotation::D2),
+            ('R', false) => (Rotation::R, Rotation::Rr, Rotation::R2),
+            ('L', false) => (Rotation::L, Rotation::Lr, Rotation::L2),
+            ('F', false) => (Rotation::F, Rotation::Fr, Rotation::F2),
+            ('B', false) => (Rotation::B, Rotation::Br, Rotation::B2),
+            ('U', true) => (Rotation::Uw, Rotation::Uwr, Rotation::Uw2),
+            ('D', true) => (Rotation::Dw, Rotation::Dwr, Rotation::Dw2),
+            ('R', true) => (Rotation::Rw, Rotation::Rwr, Rotation::Rw2),
+            ('L', true) => (Rotation::Lw, Rotation::Lwr, Rotation::Lw2),
+            ('F', true) => (Rotation::Fw, Rotation::Fwr, Rotation::Fw2),
+            ('B', true) => (Rotation::Bw, Rotation::Bwr, Rotation::Bw2),
+            _ if letter == 'M' => (Rotation::M, Rotation::Mr, Rotation::M2),
+            _ if letter == 'E' => (Rotation::E, Rotation::Er, Rotation::E2),
+            _ if letter == 'S' => (Rotation::S, Rotation::Sr, Rotation::S2),
+            _ if letter == 'x' => (Rotation::X, Rotation::Xr, Rotation::X2),
+            _ if letter == 'y' => (Rotation::Y, Rotation::Yr, Rotation::Y2),
+            _ if letter == 'z' => (Rotation::Z, Rotation::Zr, Rotation::Z2),
+            _ => return Err(letter_error),
+        };
+
+        let modifier_line = self.line;
+        let modifier_column = self.column;
+        let mut modifier = String::new();
+        while let Some(c) = self.peek() {
+            if !matches!(c, '\'' | '\u{2019}' | '0'..='9') {
+                break;
+            }
+            modifier.push(c);
+            self.advance();
+        }
+
+        match modifier.as_str() {
+            "" => Ok(clockwise),
+            "'" | "\u{2019}" => Ok(counter_clockwise),
+            "2" | "2'" | "2\u{2019}" => Ok(half),
+            _ => Err(ParseError {
+                line: modifier_line,
+                column: modifier_column,
+                length: modifier.chars().count(),
+                kind: ParseErrorKind::InvalidModifier(modifier),
+            }),
+        }
+    }
+}
+
+
+// Parses a move sequence in Singmaster notation, e.g. a WCA scramble.
+// Moves may be separated by whitespace, commas or nothing at all.
+pub fn parse_algorithm(input: &str) -> Result<Vec<Rotation>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut parser = Parser::new(&chars);
+    let mut rotations = vec![];
+    parser.skip_separators(true);
+    while parser.peek().is_some() {
+        rotations.push(parser.parse_move()?);
+        parser.skip_separators(true);
+    }
+    Ok(rotations)
+}
+
+pub fn format_algorithm(rotations: &[Rotation]) -> String {
+    rotations.iter().map(|rotation| rotation.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_face_turns() {
+        let rotations = parse_algorithm("R U R' U' D' F2").unwrap();
+        assert_eq!(rotations, vec![Rotation::R, Rotation::U, Rotation::Rr, Rotation::Ur, Rotation::Dr, Rotation::F2]);
+        assert_eq!(format_algorithm(&rotations), "R U R' U' D' F2");
+        assert_eq!("B'".parse::<Rotation>(), Ok(Rotation::Br));
+    }
+
+    #[test]
+    fn accepts_missing_separators_and_commas() {
+        assert_eq!(parse_algorithm("RUR'U'").unwrap(), parse_algorithm("R, U, R', U'").unwrap());
+        assert_eq!(parse_algorithm("  ").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn parses_half_turns() {
+        assert_eq!(parse_algorithm("U2 R2' F2").unwrap(), vec![Rotation::U2, Rotation::R2, Rotation::F2]);
+        assert_eq!(Rotation::D2.to_string(), "D2");
+    }
+
+    #[test]
+    fn parses_slices_and_rotations() {
+        assert_eq!(parse_algorithm("M' E2 S x y' z2").unwrap(), vec![Rotation::Mr, Rotation::E2, Rotation::S, Rotation::X, Rotation::Yr, Rotation::Z2]);
+        assert_eq!(format_algorithm(&[Rotation::Xr, Rotation::M2]), "x' M2");
+    }
+
+    #[test]
+    fn parses_wide_moves() {
+        assert_eq!(parse_algorithm("r Rw' 2Rw2 u'").unwrap(), vec![Rotation::Rw, Rotation::Rwr, Rotation::Rw2, Rotation::Uwr]);
+        let error = parse_algorithm("R 3Rw").unwrap_err();
+        assert_eq!((error.line, error.column, error.length), (1, 3, 1));
+        assert_eq!(error.kind, ParseErrorKind::UnsupportedLayers("3".to_string()));
+    }
+}
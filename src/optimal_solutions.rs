@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use crate::cube::*;
+use crate::simplify::decompose;
+use crate::solver::{SearchLimits, SolveError, SolverConfig};
+use crate::symmetry::Symmetry;
+
+
+// Every cube up to a number of moves from the start cube, with how many moves
+// away it is and every move that reaches it that soon, as bits by the index
+// of the move in the rotations searched with
+type Parents = HashMap<Key, (u8, u32)>;
+
+
+// Cubes reached from the start cube a layer at a time, recording all moves
+// that reach a cube of the next layer instead of only the first one. Only
+// the moves that can never be part of a shortest solution are skipped.
+fn layers_from_start(
+    start_cube: &Cube,
+    rotations: &[Rotation],
+    metric: Metric,
+    depth: usize,
+    limits: &SearchLimits,
+) -> Result<(Parents, Vec<Cube>), SolveError> {
+    let turns: Vec<(usize, usize, u8)> = rotations.iter().map(decompose).collect();
+    let mut parents: Parents = HashMap::new();
+    parents.insert(start_cube.get_key(), (0, 0));
+    let mut old_cubes: Vec<(Cube, LastTurn)> = vec![(start_cube.clone(), None)];
+
+    for step in 1..=depth as u8 {
+        let mut new_cubes = Vec::new();
+        for (cube, last_turn) in old_cubes.iter() {
+            let bytes = parents.capacity() * (size_of::<(Key, (u8, u32))>() + 1)
+                + (old_cubes.len() + new_cubes.len()) * size_of::<(Cube, LastTurn)>();
+            limits.check(parents.len(), bytes)?;
+            for (i, (rotation, turn)) in rotations.iter().zip(turns.iter()).enumerate() {
+                if is_redundant(*last_turn, *turn, metric, false) {
+                    continue;
+                }
+                let rotated_cube = cube.rotate(rotation);
+                match parents.get_mut(&rotated_cube.get_key()) {
+                    Some((other_step, moves)) => {
+                        if *other_step == step {
+                            *moves |= 1 << i;
+                        }
+                    },
+                    None => {
+                        parents.insert(rotated_cube.get_key(), (step, 1 << i));
+                        new_cubes.push((rotated_cube, next_last_turn(*last_turn, *turn)));
+                    },
+                }
+            }
+        }
+        old_cubes = new_cubes;
+    }
+
+    Ok((parents, old_cubes.into_iter().map(|(cube, _)| cube).collect()))
+}
+
+
+// Every shortest solution, one at a time. Each solution goes through exactly
+// one cube of the middle layer of cubes (as many moves from the start cube as
+// the search from it went), all ways there from the start cube are combined
+// with all ways from there down the distances to the end cube.
+pub struct OptimalSolutions {
+    rotations: Vec<Rotation>,
+    symmetries: Vec<&'static Symmetry>,
+    parents: Parents,
+    end_keys: Visited,
+    middle_cubes: std::vec::IntoIter<Cube>,
+    // Solutions through the middle cube handled last that were not returned yet
+    pending: Vec<Vec<Rotation>>,
+    pub length: usize,
+}
+
+impl OptimalSolutions {
+    // Moves from the start cube to the middle layer
+    fn middle_length(&self) -> usize {
+        self.length / 2
+    }
+
+    // All ways from the start cube to `cube`, backwards through its parents
+    fn paths_from_start(&self, cube: &Cube) -> Vec<Vec<Rotation>> {
+        let (step, moves) = self.parents[&cube.get_key()];
+        if step == 0 {
+            return vec![vec![]]
+        }
+        let mut paths = vec![];
+        for (i, rotation) in self.rotations.iter().enumerate() {
+            if moves & (1 << i) == 0 {
+                continue;
+            }
+            for mut path in self.paths_from_start(&cube.rotate(&rotation.reverse())) {
+                path.push(rotation.clone());
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    // All ways from `cube` to the end cube that take a move closer each time
+    fn paths_to_end(&self, cube: &Cube, distance: u8) -> Vec<Vec<Rotation>> {
+        if distance == 0 {
+            return vec![vec![]]
+        }
+        let mut paths = vec![];
+        for rotation in self.rotations.iter() {
+            let rotated_cube = cube.rotate(rotation);
+            if self.end_keys.get(&rotated_cube.symmetry_key(&self.symmetries)).is_some_and(|(_, other)| *other == distance - 1) {
+                for path in self.paths_to_end(&rotated_cube, distance - 1) {
+                    let mut full_path = vec![rotation.clone()];
+                    full_path.extend(path);
+                    paths.push(full_path);
+                }
+            }
+        }
+        paths
+    }
+}
+
+impl Iterator for OptimalSolutions {
+    type Item = Vec<Rotation>;
+
+    fn next(&mut self) -> Option<Vec<Rotation>> {
+        while self.pending.is_empty() {
+            let middle_cube = self.middle_cubes.next()?;
+            let distance = match self.end_keys.get(&middle_cube.symmetry_key(&self.symmetries)) {
+                Some((_, distance)) if *distance as usize + self.middle_length() == self.length => *distance,
+                _ => continue,
+            };
+            let to_end = self.paths_to_end(&middle_cube, distance);
+            for from_start in self.paths_from_start(&middle_cube) {
+                for path in to_end.iter() {
+                    let mut solution = from_start.clone();
+                    solution.extend(path.iter().cloned());
+                    self.pending.push(solution);
+                }
+            }
+            self.pending.reverse();
+        }
+        self.pending.pop()
+    }
+}
+
+
+// Every shortest solution instead of only the first one found. Finds the
+// length with the two way search first, so a config with limits limits both.
+pub fn optimal_solutions(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    metric: Metric,
+    config: &SolverConfig,
+) -> Result<OptimalSolutions, SolveError> {
+    let length = solve_cube_two_way_breath_first(start_cube, end_cube, metric, config)?.len();
+    let limits = SearchLimits::new(config);
+    let rotations = config.rotations(metric)?;
+    let end_keys = distances_to_end(end_cube, metric, length - length / 2, &limits)?;
+    let (parents, middle_cubes) = layers_from_start(start_cube, &rotations, metric, length / 2, &limits)?;
+    Ok(OptimalSolutions {
+        symmetries: search_symmetries(end_cube, &rotations),
+        rotations,
+        parents,
+        end_keys,
+        middle_cubes: middle_cubes.into_iter(),
+        pending: vec![],
+        length,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::notation::{format_algorithm, parse_algorithm};
+
+    fn solutions(scramble: &str, metric: Metric) -> HashSet<String> {
+        let start_cube = Cube::new().rotate_all(&parse_algorithm(scramble).unwrap());
+        let solutions = optimal_solutions(&start_cube, &Cube::new(), metric, &SolverConfig::new()).unwrap();
+        solutions.map(|solution| format_algorithm(&solution)).collect()
+    }
+
+    // Every sequence of `length` moves that solves the cube
+    fn brute_force(scramble: &str, metric: Metric, length: usize) -> HashSet<String> {
+        let start_cube = Cube::new().rotate_all(&parse_algorithm(scramble).unwrap());
+        let mut sequences: Vec<Vec<Rotation>> = vec![vec![]];
+        for _ in 0..length {
+            sequences = sequences.iter().flat_map(|sequence| metric.rotations().into_iter().map(move |rotation| {
+                let mut sequence = sequence.clone();
+                sequence.push(rotation);
+                sequence
+            })).collect();
+        }
+        sequences.iter()
+            .filter(|sequence| start_cube.rotate_all(sequence) == Cube::new())
+            .map(|sequence| format_algorithm(sequence))
+            .collect()
+    }
+
+    #[test]
+    fn counts_every_order_of_commuting_moves() {
+        let expected: HashSet<String> = ["R' L'", "L' R'"].iter().map(|solution| solution.to_string()).collect();
+        assert_eq!(solutions("R L", Metric::HalfTurn), expected);
+        assert_eq!(solutions("R U", Metric::HalfTurn).len(), 1);
+        // R2 is R R or R' R' in the quarter turn metric
+        assert_eq!(solutions("R2", Metric::QuarterTurn).len(), 2);
+    }
+
+    #[test]
+    fn finds_what_brute_force_finds() {
+        assert_eq!(solutions("R U F' L", Metric::HalfTurn), brute_force("R U F' L", Metric::HalfTurn, 4));
+        assert_eq!(solutions("U D2 R", Metric::HalfTurn), brute_force("U D2 R", Metric::HalfTurn, 3));
+        assert_eq!(solutions("R2 U", Metric::QuarterTurn), brute_force("R2 U", Metric::QuarterTurn, 3));
+    }
+}
@@ -0,0 +1,262 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use crate::cube::{Metric, Rotation};
+use crate::cubie::CubieCube;
+use crate::coordinates::{arrangement_from_rank, arrangement_rank};
+use crate::symmetry::{conjugate_rotation, whole_cube_symmetry};
+use crate::table_file::{load_or_build, read_tables, write_tables, TableError, TableSource};
+
+
+// Distances packed two to a byte, 15 marks states not reached yet
+pub(crate) struct NibbleTable {
+    pub(crate) data: Vec<u8>,
+}
+
+impl NibbleTable {
+    pub(crate) fn new(len: usize) -> NibbleTable {
+        NibbleTable { data: vec![0xFF; len.div_ceil(2)] }
+    }
+
+    pub(crate) fn get(&self, index: usize) -> u8 {
+        (self.data[index / 2] >> (4 * (index % 2))) & 0xF
+    }
+
+    pub(crate) fn set(&mut self, index: usize, value: u8) {
+        let shift = 4 * (index % 2);
+        self.data[index / 2] = (self.data[index / 2] & !(0xF << shift)) | (value << shift);
+    }
+
+    // Breadth first search over all indices from `starts`, a level at a time.
+    // `next` gives the index a move leads to and `costs` what each move costs.
+    // Distances of 15 and more are all stored as 15, still a lower bound.
+    pub(crate) fn fill(&mut self, len: usize, starts: &[usize], costs: &[u8], next: impl Fn(usize, usize) -> usize) {
+        for start in starts.iter() {
+            self.set(*start, 0);
+        }
+        let mut deepest = 0;
+        let mut depth = 0;
+        while depth <= deepest && depth < 15 {
+            for index in 0..len {
+                if self.get(index) != depth {
+                    continue;
+                }
+                for (rotation, cost) in costs.iter().enumerate() {
+                    let next_depth = depth + cost;
+                    let next_index = next(index, rotation);
+                    if next_depth < self.get(next_index) {
+                        self.set(next_index, next_depth);
+                        deepest = deepest.max(next_depth);
+                    }
+                }
+            }
+            depth += 1;
+        }
+    }
+}
+
+
+pub const PATTERN_DATABASE_KIND: &str = "korf";
+
+pub(crate) const CORNER_STATES: usize = 40320 * 2187;
+pub(crate) const EDGE_GROUP_STATES: usize = 665280 * 64;
+
+// The edges are split in two groups of six, the U layer with FR and FL and
+// the D layer with BL and BR
+pub(crate) const EDGE_GROUPS: [[u8; 6]; 2] = [[0, 1, 2, 3, 8, 9], [4, 5, 6, 7, 10, 11]];
+
+
+pub(crate) fn corner_index(cube: &CubieCube) -> usize {
+    cube.corner_permutation_rank() as usize * 2187 + cube.twist() as usize
+}
+
+// Where the edges of a group are and how they are flipped there
+pub(crate) fn edge_group_index(cube: &CubieCube, group: usize) -> usize {
+    let mut positions = [0; 6];
+    let mut flips = 0;
+    for position in 0..12 {
+        let edge = cube.edge_permutation[position];
+        if let Some(slot) = EDGE_GROUPS[group].iter().position(|other| *other == edge) {
+            positions[slot] = position as u8;
+            flips |= (cube.edge_orientation[position] as usize) << slot;
+        }
+    }
+    arrangement_rank(&positions, 12) * 64 + flips
+}
+
+
+// Exact distances to solved for the corners alone and for each half of the
+// edges, so the largest of the three never overestimates (Korf 1997). The
+// move tables let a search follow the three indices without cubies.
+// The x2 rotation turns the second group of edges into the first one, so the
+// second group is looked up in the table of the first one with the cube
+// conjugated by x2 and every move turned into its conjugate.
+pub struct PatternDatabases {
+    pub metric: Metric,
+    pub(crate) rotations: Vec<Rotation>,
+    pub(crate) corner_permutation_moves: Vec<u16>,
+    pub(crate) twist_moves: Vec<u16>,
+    // New arrangement rank in the low 20 bits and the flipped slots above
+    pub(crate) arrangement_moves: Vec<u32>,
+    // Index of the conjugate of each move
+    pub(crate) conjugate_moves: Vec<usize>,
+    pub(crate) corners: NibbleTable,
+    pub(crate) edges: NibbleTable,
+}
+
+impl PatternDatabases {
+    pub(crate) fn with_move_tables(metric: Metric) -> PatternDatabases {
+        let rotations = metric.rotations();
+        let moves: Vec<CubieCube> = rotations.iter().map(|rotation| CubieCube::new().rotate(rotation)).collect();
+        let symmetry = whole_cube_symmetry(&Rotation::X2);
+        let conjugate_moves = rotations.iter().map(|rotation| {
+            let conjugate = conjugate_rotation(rotation, &symmetry);
+            rotations.iter().position(|other| *other == conjugate).unwrap()
+        }).collect();
+        PatternDatabases {
+            metric,
+            corner_permutation_moves: corner_permutation_moves(&moves),
+            twist_moves: twist_moves(&moves),
+            arrangement_moves: arrangement_moves(&moves),
+            rotations,
+            conjugate_moves,
+            corners: NibbleTable::new(0),
+            edges: NibbleTable::new(0),
+        }
+    }
+
+    // Takes about 12 seconds in release builds and 65 MB
+    pub fn new(metric: Metric) -> PatternDatabases {
+        let mut databases = PatternDatabases::with_move_tables(metric);
+        let solved = databases.indices(&CubieCube::new());
+        let costs = vec![1; databases.rotations.len()];
+        let mut corners = NibbleTable::new(CORNER_STATES);
+        corners.fill(CORNER_STATES, &[solved[0]], &costs, |index, rotation| databases.rotate_corners(index, rotation));
+        let mut edges = NibbleTable::new(EDGE_GROUP_STATES);
+        edges.fill(EDGE_GROUP_STATES, &[solved[1]], &costs, |index, rotation| databases.rotate_edges(index, rotation));
+        databases.corners = corners;
+        databases.edges = edges;
+        databases
+    }
+
+    pub fn load(path: &Path, metric: Metric) -> Result<PatternDatabases, TableError> {
+        let mut databases = PatternDatabases::with_move_tables(metric);
+        let lens = [CORNER_STATES, EDGE_GROUP_STATES];
+        let mut tables = read_tables(path, PATTERN_DATABASE_KIND, metric, &lens)?.into_iter();
+        databases.corners = tables.next().unwrap();
+        databases.edges = tables.next().unwrap();
+        Ok(databases)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), TableError> {
+        write_tables(path, PATTERN_DATABASE_KIND, self.metric, &[&self.corners, &self.edges])
+    }
+
+    // Corner index, the index of the first edge group and the index of the
+    // first edge group of the conjugated cube
+    pub(crate) fn indices(&self, cube: &CubieCube) -> [usize; 3] {
+        let conjugate = cube.conjugate(&whole_cube_symmetry(&Rotation::X2));
+        [corner_index(cube), edge_group_index(cube, 0), edge_group_index(&conjugate, 0)]
+    }
+
+    pub(crate) fn rotate_corners(&self, index: usize, rotation: usize) -> usize {
+        let moves = self.rotations.len();
+        let permutation = self.corner_permutation_moves[index / 2187 * moves + rotation] as usize;
+        let twist = self.twist_moves[index % 2187 * moves + rotation] as usize;
+        permutation * 2187 + twist
+    }
+
+    pub(crate) fn rotate_edges(&self, index: usize, rotation: usize) -> usize {
+        let next = self.arrangement_moves[index / 64 * self.rotations.len() + rotation] as usize;
+        (next & 0xFFFFF) * 64 + ((index % 64) ^ (next >> 20))
+    }
+
+    pub(crate) fn rotate_indices(&self, indices: [usize; 3], rotation: usize) -> [usize; 3] {
+        [
+            self.rotate_corners(indices[0], rotation),
+            self.rotate_edges(indices[1], rotation),
+            self.rotate_edges(indices[2], self.conjugate_moves[rotation]),
+        ]
+    }
+
+    pub(crate) fn estimate(&self, indices: [usize; 3]) -> u8 {
+        let corners = self.corners.get(indices[0]);
+        let edges_0 = self.edges.get(indices[1]);
+        let edges_1 = self.edges.get(indices[2]);
+        corners.max(edges_0).max(edges_1)
+    }
+
+    // A lower bound for the number of moves needed to solve the cube
+    pub fn distance(&self, cube: &CubieCube) -> u8 {
+        self.estimate(self.indices(cube))
+    }
+}
+
+
+// The databases solve_cube_ida_star uses and where they came from, loaded or
+// built on first use
+pub fn pattern_databases(metric: Metric) -> &'static (PatternDatabases, TableSource) {
+    static QUARTER_TURN_DATABASES: OnceLock<(PatternDatabases, TableSource)> = OnceLock::new();
+    static HALF_TURN_DATABASES: OnceLock<(PatternDatabases, TableSource)> = OnceLock::new();
+    let databases = match metric {
+        Metric::QuarterTurn => &QUARTER_TURN_DATABASES,
+        Metric::HalfTurn => &HALF_TURN_DATABASES,
+    };
+    databases.get_or_init(|| load_or_build(PATTERN_DATABASE_KIND, metric, PatternDatabases::load, PatternDatabases::new))
+}
+
+
+// Corner permutation and twist change independently of each other
+fn corner_permutation_moves(moves: &[CubieCube]) -> Vec<u16> {
+    let mut permutation_moves = vec![0; 40320 * moves.len()];
+    let mut cube = CubieCube::new();
+    for rank in 0..40320 {
+        cube.set_corner_permutation_rank(rank as u16);
+        for (i, rotation) in moves.iter().enumerate() {
+            permutation_moves[rank * moves.len() + i] = cube.multiply(rotation).corner_permutation_rank();
+        }
+    }
+    permutation_moves
+}
+
+fn twist_moves(moves: &[CubieCube]) -> Vec<u16> {
+    let mut twist_moves = vec![0; 2187 * moves.len()];
+    let mut cube = CubieCube::new();
+    for twist in 0..2187 {
+        cube.set_twist(twist as u16);
+        for (i, rotation) in moves.iter().enumerate() {
+            twist_moves[twist * moves.len() + i] = cube.multiply(rotation).twist();
+        }
+    }
+    twist_moves
+}
+
+// Moves act on the positions of a group of six edges the same way whichever
+// edges are in the group, so both groups share this table
+fn arrangement_moves(moves: &[CubieCube]) -> Vec<u32> {
+    // Where a move takes the edge at each position and whether it flips it there
+    let targets: Vec<[(u8, u8); 12]> = moves.iter().map(|rotation| {
+        let mut targets = [(0, 0); 12];
+        for position in 0..12 {
+            let from = rotation.edge_permutation[position] as usize;
+            targets[from] = (position as u8, rotation.edge_orientation[position]);
+        }
+        targets
+    }).collect();
+
+    let mut arrangement_moves = vec![0; 665280 * moves.len()];
+    let mut positions = [0; 6];
+    for rank in 0..665280 {
+        arrangement_from_rank(rank, 12, &mut positions);
+        for (i, rotation_targets) in targets.iter().enumerate() {
+            let mut flips = 0;
+            let mut new_positions = [0; 6];
+            for slot in 0..6 {
+                let (position, flip) = rotation_targets[positions[slot] as usize];
+                new_positions[slot] = position;
+                flips |= (flip as u32) << slot;
+            }
+            arrangement_moves[rank * moves.len() + i] = arrangement_rank(&new_positions, 12) as u32 | flips << 20;
+        }
+    }
+    arrangement_moves
+}
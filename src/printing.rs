@@ -1,6 +1,8 @@
 use colored::Colorize;
 use std::process::abort;
 use crate::cube::{Cube, Rotation};
+use crate::metrics::MoveCount;
+use crate::notation::format_algorithm;
 
 
 fn colorize(color: u8) -> colored::ColoredString {
@@ -23,22 +25,22 @@ impl Cube {
     //     print!("{:02} ", self.sides[0].colors[0]);
     //     print!("{:02} ", self.sides[0].colors[1]);
     //     print!("{:02} ", self.sides[0].colors[2]);
-    //     println!("");
+    //     println!();
     //     for _ in 0..10 {
     //         print!(" ")
     //     }
     //     print!("{:02} ", self.sides[0].colors[3]);
     //     print!("{:02} ", 0);
     //     print!("{:02} ", self.sides[0].colors[4]);
-    //     println!("");
+    //     println!();
     //     for _ in 0..10 {
     //         print!(" ")
     //     }
     //     print!("{:02} ", self.sides[0].colors[5]);
     //     print!("{:02} ", self.sides[0].colors[6]);
     //     print!("{:02} ", self.sides[0].colors[7]);
-    //     println!("");
-    //     println!("");
+    //     println!();
+    //     println!();
     //     // Sides 1-4
     //     for i in 1..5 {
     //         print!("{:02} ", self.sides[i].colors[0]);
@@ -46,22 +48,22 @@ impl Cube {
     //         print!("{:02} ", self.sides[i].colors[2]);
     //         print!(" ");
     //     }
-    //     println!("");
+    //     println!();
     //     for i in 1..5 {
     //         print!("{:02} ", self.sides[i].colors[3]);
     //         print!("{:02} ", i);
     //         print!("{:02} ", self.sides[i].colors[4]);
     //         print!(" ");
     //     }
-    //     println!("");
+    //     println!();
     //     for i in 1..5 {
     //         print!("{:02} ", self.sides[i].colors[5]);
     //         print!("{:02} ", self.sides[i].colors[6]);
     //         print!("{:02} ", self.sides[i].colors[7]);
     //         print!(" ");
     //     }
-    //     println!("");
-    //     println!("");
+    //     println!();
+    //     println!();
     //     // Side 5
     //     for _ in 0..10 {
     //         print!(" ")
@@ -69,21 +71,21 @@ impl Cube {
     //     print!("{:02} ", self.sides[5].colors[0]);
     //     print!("{:02} ", self.sides[5].colors[1]);
     //     print!("{:02} ", self.sides[5].colors[2]);
-    //     println!("");
+    //     println!();
     //     for _ in 0..10 {
     //         print!(" ")
     //     }
     //     print!("{:02} ", self.sides[5].colors[3]);
     //     print!("{:02} ", 5);
     //     print!("{:02} ", self.sides[5].colors[4]);
-    //     println!("");
+    //     println!();
     //     for _ in 0..10 {
     //         print!(" ")
     //     }
     //     print!("{:02} ", self.sides[5].colors[5]);
     //     print!("{:02} ", self.sides[5].colors[6]);
     //     print!("{:02} ", self.sides[5].colors[7]);
-    //     println!("");
+    //     println!();
     // }
     //
     // fn print_diff(&self, other: &Cube) {
@@ -94,22 +96,22 @@ impl Cube {
     //     print!("{}", if self.sides[0].colors[0] == other.sides[0].colors[0] {"."} else {"#"});
     //     print!("{}", if self.sides[0].colors[1] == other.sides[0].colors[1] {"."} else {"#"});
     //     print!("{}", if self.sides[0].colors[2] == other.sides[0].colors[2] {"."} else {"#"});
-    //     println!("");
+    //     println!();
     //     for _ in 0..4 {
     //         print!(" ")
     //     }
     //     print!("{}", if self.sides[0].colors[3] == other.sides[0].colors[3] {"."} else {"#"});
     //     print!(".");
     //     print!("{}", if self.sides[0].colors[4] == other.sides[0].colors[4] {"."} else {"#"});
-    //     println!("");
+    //     println!();
     //     for _ in 0..4 {
     //         print!(" ")
     //     }
     //     print!("{}", if self.sides[0].colors[5] == other.sides[0].colors[5] {"."} else {"#"});
     //     print!("{}", if self.sides[0].colors[6] == other.sides[0].colors[6] {"."} else {"#"});
     //     print!("{}", if self.sides[0].colors[7] == other.sides[0].colors[7] {"."} else {"#"});
-    //     println!("");
-    //     println!("");
+    //     println!();
+    //     println!();
     //     // Sides 1-4
     //     for i in 1..5 {
     //         print!("{}", if self.sides[i].colors[0] == other.sides[i].colors[0] {"."} else {"#"});
@@ -117,22 +119,22 @@ impl Cube {
     //         print!("{}", if self.sides[i].colors[2] == other.sides[i].colors[2] {"."} else {"#"});
     //         print!(" ");
     //     }
-    //     println!("");
+    //     println!();
     //     for i in 1..5 {
     //         print!("{}", if self.sides[i].colors[3] == other.sides[i].colors[3] {"."} else {"#"});
     //         print!(".");
     //         print!("{}", if self.sides[i].colors[4] == other.sides[i].colors[4] {"."} else {"#"});
     //         print!(" ");
     //     }
-    //     println!("");
+    //     println!();
     //     for i in 1..5 {
     //         print!("{}", if self.sides[i].colors[5] == other.sides[i].colors[5] {"."} else {"#"});
     //         print!("{}", if self.sides[i].colors[6] == other.sides[i].colors[6] {"."} else {"#"});
     //         print!("{}", if self.sides[i].colors[7] == other.sides[i].colors[7] {"."} else {"#"});
     //         print!(" ");
     //     }
-    //     println!("");
-    //     println!("");
+    //     println!();
+    //     println!();
     //     // Side 5
     //     for _ in 0..4 {
     //         print!(" ")
@@ -140,21 +142,21 @@ impl Cube {
     //     print!("{}", if self.sides[5].colors[0] == other.sides[5].colors[0] {"."} else {"#"});
     //     print!("{}", if self.sides[5].colors[1] == other.sides[5].colors[1] {"."} else {"#"});
     //     print!("{}", if self.sides[5].colors[2] == other.sides[5].colors[2] {"."} else {"#"});
-    //     println!("");
+    //     println!();
     //     for _ in 0..4 {
     //         print!(" ")
     //     }
     //     print!("{}", if self.sides[5].colors[3] == other.sides[5].colors[3] {"."} else {"#"});
     //     print!(".");
     //     print!("{}", if self.sides[5].colors[4] == other.sides[5].colors[4] {"."} else {"#"});
-    //     println!("");
+    //     println!();
     //     for _ in 0..4 {
     //         print!(" ")
     //     }
     //     print!("{}", if self.sides[5].colors[5] == other.sides[5].colors[5] {"."} else {"#"});
     //     print!("{}", if self.sides[5].colors[6] == other.sides[5].colors[6] {"."} else {"#"});
     //     print!("{}", if self.sides[5].colors[7] == other.sides[5].colors[7] {"."} else {"#"});
-    //     println!("");
+    //     println!();
     // }
 
     fn print(&self) {
@@ -162,7 +164,7 @@ impl Cube {
     }
 
     fn print_rot(&self, rotation: &Rotation) {
-        self.print_rot_op(Some(&rotation))
+        self.print_rot_op(Some(rotation))
     }
 
     fn print_rot_op(&self, rotation: Option<&Rotation>) {
@@ -172,26 +174,27 @@ impl Cube {
         print!("{}", colorize(self.get_at(0, 0)));
         print!("{}", colorize(self.get_at(0, 1)));
         print!("{}", colorize(self.get_at(0, 2)));
-        println!("");
+        println!();
         for _ in 0..4 {
             print!(" ")
         }
         print!("{}", colorize(self.get_at(0, 3)));
         match rotation {
-            Some(Rotation::Dr) => {print!("{}", '\u{21A9}');}
-            Some(Rotation::D) => {print!("{}", '\u{21AA}');}
-            _ => {print!("{}", colorize(0));}
+            Some(Rotation::D) => {print!("\u{21A9}");}
+            Some(Rotation::Dr) => {print!("\u{21AA}");}
+            Some(Rotation::D2) => {print!("2");}
+            _ => {print!("{}", colorize(self.get_at(0, 4)));}
         }
         print!("{}", colorize(self.get_at(0, 5)));
-        println!("");
+        println!();
         for _ in 0..4 {
             print!(" ")
         }
         print!("{}", colorize(self.get_at(0, 6)));
         print!("{}", colorize(self.get_at(0, 7)));
         print!("{}", colorize(self.get_at(0, 8)));
-        println!("");
-        println!("");
+        println!();
+        println!();
         // Sides 1-4
         for i in 1..5 {
             print!("{}", colorize(self.get_at(i, 0)));
@@ -199,7 +202,7 @@ impl Cube {
             print!("{}", colorize(self.get_at(i, 2)));
             print!(" ");
         }
-        println!("");
+        println!();
         for i in 1..5 {
             print!("{}", colorize(self.get_at(i, 3)));
             match i {
@@ -207,14 +210,16 @@ impl Cube {
                     match rotation {
                         Some(Rotation::L) => {print!("{}", '\u{21A9}'.to_string().magenta());}
                         Some(Rotation::Lr) => {print!("{}", '\u{21AA}'.to_string().magenta());}
-                        _ => {print!("{}", colorize(i as u8));}
+                        Some(Rotation::L2) => {print!("{}", "2".magenta());}
+                        _ => {print!("{}", colorize(self.get_at(i, 4)));}
                     }
                 },
                 2 => {
                     match rotation {
                         Some(Rotation::B) => {print!("{}", '\u{21A9}'.to_string().green());}
                         Some(Rotation::Br) => {print!("{}", '\u{21AA}'.to_string().green());}
-                        _ => {print!("{}", colorize(i as u8));}
+                        Some(Rotation::B2) => {print!("{}", "2".green());}
+                        _ => {print!("{}", colorize(self.get_at(i, 4)));}
                     }
                 },
                 3 => {
@@ -222,14 +227,16 @@ impl Cube {
                     match rotation {
                         Some(Rotation::R) => {print!("{}", '\u{21A9}'.to_string().red());}
                         Some(Rotation::Rr) => {print!("{}", '\u{21AA}'.to_string().red());}
-                        _ => {print!("{}", colorize(i as u8));}
+                        Some(Rotation::R2) => {print!("{}", "2".red());}
+                        _ => {print!("{}", colorize(self.get_at(i, 4)));}
                     }
                 },
                 4 => {
                     match rotation {
                         Some(Rotation::F) => {print!("{}", '\u{21A9}'.to_string().blue());}
                         Some(Rotation::Fr) => {print!("{}", '\u{21AA}'.to_string().blue());}
-                        _ => {print!("{}", colorize(i as u8));}
+                        Some(Rotation::F2) => {print!("{}", "2".blue());}
+                        _ => {print!("{}", colorize(self.get_at(i, 4)));}
                     }
                 },
                 _ => {
@@ -239,15 +246,15 @@ impl Cube {
             print!("{}", colorize(self.get_at(i, 5)));
             print!(" ");
         }
-        println!("");
+        println!();
         for i in 1..5 {
             print!("{}", colorize(self.get_at(i, 6)));
             print!("{}", colorize(self.get_at(i, 7)));
             print!("{}", colorize(self.get_at(i, 8)));
             print!(" ");
         }
-        println!("");
-        println!("");
+        println!();
+        println!();
         // Side 5
         for _ in 0..4 {
             print!(" ")
@@ -255,7 +262,7 @@ impl Cube {
         print!("{}", colorize(self.get_at(5, 0)));
         print!("{}", colorize(self.get_at(5, 1)));
         print!("{}", colorize(self.get_at(5, 2)));
-        println!("");
+        println!();
         for _ in 0..4 {
             print!(" ")
         }
@@ -263,32 +270,64 @@ impl Cube {
         match rotation {
             Some(Rotation::U) => {print!("{}", '\u{21A9}'.to_string().yellow());}
             Some(Rotation::Ur) => {print!("{}", '\u{21AA}'.to_string().yellow());}
-            _ => {print!("{}", colorize(5));}
+            Some(Rotation::U2) => {print!("{}", "2".yellow());}
+            _ => {print!("{}", colorize(self.get_at(5, 4)));}
         }
         print!("{}", colorize(self.get_at(5, 5)));
-        println!("");
+        println!();
         for _ in 0..4 {
             print!(" ")
         }
         print!("{}", colorize(self.get_at(5, 6)));
         print!("{}", colorize(self.get_at(5, 7)));
         print!("{}", colorize(self.get_at(5, 8)));
-        println!("");
+        println!();
     }
 }
 
-pub fn print_solution(start_cube: &Cube, rotations: &Vec<Rotation>) {
+// `steps` are the name and number of moves of each step the solution is made
+// of, every step is labeled above the cube it starts from. Empty for a
+// solution without steps.
+pub fn print_solution(start_cube: &Cube, rotations: &[Rotation], steps: &[(&str, usize)]) {
+    let mut step_starts = vec![];
+    let mut start = 0;
+    for (name, moves) in steps.iter() {
+        step_starts.push((start, *name));
+        start += moves;
+    }
     let mut cube = start_cube.clone();
-    for rotation in rotations.iter() {
+    for (i, rotation) in rotations.iter().enumerate() {
+        for (_, name) in step_starts.iter().filter(|(start, _)| *start == i) {
+            println!("STEP {}", name);
+        }
         cube.print_rot(rotation);
         println!();
         cube = cube.rotate(rotation);
     }
+    for (_, name) in step_starts.iter().filter(|(start, _)| *start == rotations.len()) {
+        println!("STEP {}", name);
+    }
     cube.print();
 
-    println!("SOLUTION MOVES:");
+    println!(
+        "SOLUTION MOVES ({} HTM, {} QTM, {} STM, {} ETM):",
+        rotations.htm(),
+        rotations.qtm(),
+        rotations.stm(),
+        rotations.etm(),
+    );
     for rotation in rotations.iter() {
-        print!("{:?} ", rotation);
+        print!("{} ", rotation);
+    }
+    println!();
+}
+
+// Like print_solution with every phase labeled, followed by the moves of each
+// phase on their own line
+pub fn print_phased_solution(start_cube: &Cube, phases: &[Vec<Rotation>], phase_names: &[&str]) {
+    let steps: Vec<(&str, usize)> = phase_names.iter().zip(phases.iter()).map(|(name, phase)| (*name, phase.len())).collect();
+    print_solution(start_cube, &phases.concat(), &steps);
+    for (phase, name) in phases.iter().zip(phase_names.iter()) {
+        println!("PHASE {} ({} moves): {}", name, phase.len(), format_algorithm(phase));
     }
-    println!("");
 }
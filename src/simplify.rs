@@ -0,0 +1,123 @@
+use crate::cube::Rotation;
+
+
+// Moves on the same axis commute with each other. Each axis has six layers
+// (both faces, the slice, both wide moves and the whole cube) listed in the
+// order they are written out, each as clockwise, half and counter-clockwise.
+fn layer_rotations(axis: usize, layer: usize) -> [Rotation; 3] {
+    match (axis, layer) {
+        (0, 0) => [Rotation::U, Rotation::U2, Rotation::Ur],
+        (0, 1) => [Rotation::D, Rotation::D2, Rotation::Dr],
+        (0, 2) => [Rotation::E, Rotation::E2, Rotation::Er],
+        (0, 3) => [Rotation::Uw, Rotation::Uw2, Rotation::Uwr],
+        (0, 4) => [Rotation::Dw, Rotation::Dw2, Rotation::Dwr],
+        (0, 5) => [Rotation::Y, Rotation::Y2, Rotation::Yr],
+        (1, 0) => [Rotation::R, Rotation::R2, Rotation::Rr],
+        (1, 1) => [Rotation::L, Rotation::L2, Rotation::Lr],
+        (1, 2) => [Rotation::M, Rotation::M2, Rotation::Mr],
+        (1, 3) => [Rotation::Rw, Rotation::Rw2, Rotation::Rwr],
+        (1, 4) => [Rotation::Lw, Rotation::Lw2, Rotation::Lwr],
+        (1, 5) => [Rotation::X, Rotation::X2, Rotation::Xr],
+        (2, 0) => [Rotation::F, Rotation::F2, Rotation::Fr],
+        (2, 1) => [Rotation::B, Rotation::B2, Rotation::Br],
+        (2, 2) => [Rotation::S, Rotation::S2, Rotation::Sr],
+        (2, 3) => [Rotation::Fw, Rotation::Fw2, Rotation::Fwr],
+        (2, 4) => [Rotation::Bw, Rotation::Bw2, Rotation::Bwr],
+        (2, 5) => [Rotation::Z, Rotation::Z2, Rotation::Zr],
+        _ => panic!("Invalid axis {} or layer {}", axis, layer),
+    }
+}
+
+// Axis, layer and the number of clockwise quarter turns of a rotation
+pub(crate) fn decompose(rotation: &Rotation) -> (usize, usize, u8) {
+    for axis in 0..3 {
+        for layer in 0..6 {
+            let rotations = layer_rotations(axis, layer);
+            for (i, other) in rotations.iter().enumerate() {
+                if other == rotation {
+                    return (axis, layer, i as u8 + 1)
+                }
+            }
+        }
+    }
+    unreachable!()
+}
+
+pub(crate) fn compose(axis: usize, layer: usize, quarter_turns: u8) -> Option<Rotation> {
+    match quarter_turns % 4 {
+        0 => None,
+        turns => Some(layer_rotations(axis, layer)[turns as usize - 1].clone()),
+    }
+}
+
+
+// Cancels inverse moves, merges repeated turns of a layer into the shortest
+// equivalent and writes moves on the same axis in a fixed order, e.g.
+// "U D U" becomes "U2 D" and "R U U' R'" disappears completely.
+pub fn simplify(rotations: &[Rotation]) -> Vec<Rotation> {
+    // Quarter turns per layer for runs of moves on the same axis
+    let mut groups: Vec<(usize, [u8; 6])> = vec![];
+
+    for rotation in rotations.iter() {
+        let (axis, layer, quarter_turns) = decompose(rotation);
+        match groups.last_mut() {
+            Some((group_axis, turns)) if *group_axis == axis => {
+                turns[layer] = (turns[layer] + quarter_turns) % 4;
+                if turns.iter().all(|turns| *turns == 0) {
+                    groups.pop();
+                }
+            },
+            _ => {
+                let mut turns = [0; 6];
+                turns[layer] = quarter_turns;
+                groups.push((axis, turns));
+            }
+        }
+    }
+
+    let mut simplified = vec![];
+    for (axis, turns) in groups.iter() {
+        for (layer, quarter_turns) in turns.iter().enumerate() {
+            if let Some(rotation) = compose(*axis, layer, *quarter_turns) {
+                simplified.push(rotation);
+            }
+        }
+    }
+    simplified
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::notation::{format_algorithm, parse_algorithm};
+
+    fn simplified(input: &str) -> String {
+        format_algorithm(&simplify(&parse_algorithm(input).unwrap()))
+    }
+
+    #[test]
+    fn cancels_and_merges() {
+        assert_eq!(simplified("R U U' R'"), "");
+        assert_eq!(simplified("U D U"), "U2 D");
+        assert_eq!(simplified("R R R"), "R'");
+        assert_eq!(simplified("D U"), "U D");
+        assert_eq!(simplified("R L R' L'"), "");
+        assert_eq!(simplified("R U R'"), "R U R'");
+    }
+
+    #[test]
+    fn keeps_what_the_moves_do() {
+        let rotations = parse_algorithm("R R' U2 M E E' D U L R x x' F").unwrap();
+        assert_eq!(Cube::new().rotate_all(&simplify(&rotations)), Cube::new().rotate_all(&rotations));
+    }
+
+    #[test]
+    fn decompose_and_compose_agree() {
+        for rotation in parse_algorithm("U D' R2 M' x y2 Rw' Fw").unwrap() {
+            let (axis, layer, quarter_turns) = decompose(&rotation);
+            assert_eq!(compose(axis, layer, quarter_turns), Some(rotation));
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use crate::cube::{Cube, Metric, Rotation};
+use crate::cubie::CubieCube;
+use crate::simplify::decompose;
+use crate::validation::ValidationError;
+
+
+// Limits for a search, so deep scrambles stop with an error instead of
+// running until they are out of memory. Limits that are None are not checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolverConfig {
+    // Longest solution searched for, in moves of the metric searched with
+    pub max_depth: usize,
+    // Cubes stored by the search
+    pub max_nodes: Option<usize>,
+    // Rough number of bytes used by the stored cubes
+    pub memory_budget: Option<usize>,
+    pub timeout: Option<Duration>,
+    // Threads used to search, the solution found is the same for any number
+    pub threads: usize,
+    // The faces the search may turn, e.g. R and U to solve with <R, U>, None
+    // for all of them
+    pub moves: Option<Vec<Rotation>>,
+    // Prints the size of every step of the search
+    pub verbose: bool,
+}
+
+impl Default for SolverConfig {
+    fn default() -> SolverConfig {
+        SolverConfig::new()
+    }
+}
+
+impl SolverConfig {
+    // 26 moves, every cube can be solved in 26 quarter turn metric moves (and
+    // 20 half turn metric moves), and a thread for every core
+    pub fn new() -> SolverConfig {
+        SolverConfig {
+            max_depth: 26,
+            max_nodes: None,
+            memory_budget: None,
+            timeout: None,
+            threads: std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+            moves: None,
+            verbose: false,
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> SolverConfig {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> SolverConfig {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn with_memory_budget(mut self, bytes: usize) -> SolverConfig {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> SolverConfig {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> SolverConfig {
+        self.threads = threads;
+        self
+    }
+
+    // Any turn of a face allows all turns of it, so [R, U] and [R', U2] are
+    // both <R, U>. Only face turns can be given, searching with a slice, wide
+    // move or rotation fails with SolveError::UnsupportedMove.
+    pub fn with_moves(mut self, moves: &[Rotation]) -> SolverConfig {
+        self.moves = Some(moves.to_vec());
+        self
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> SolverConfig {
+        self.verbose = verbose;
+        self
+    }
+
+    // The turns of the metric the search may do
+    pub fn rotations(&self, metric: Metric) -> Result<Vec<Rotation>, SolveError> {
+        let Some(moves) = &self.moves else {
+            return Ok(metric.rotations())
+        };
+        let mut layers: Vec<(usize, usize)> = vec![];
+        for rotation in moves.iter() {
+            let (axis, layer, _) = decompose(rotation);
+            // Layers 0 and 1 are the two faces of an axis
+            if layer > 1 {
+                return Err(SolveError::UnsupportedMove(rotation.clone()))
+            }
+            layers.push((axis, layer));
+        }
+        Ok(metric.rotations().into_iter().filter(|rotation| {
+            let (axis, layer, _) = decompose(rotation);
+            layers.contains(&(axis, layer))
+        }).collect())
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    InvalidCube(ValidationError),
+    // No moves turn the start cube into the end cube, e.g. when the cubes
+    // are held differently or the moves allowed cannot reach the end cube
+    Unsolvable,
+    // Only face turns can be searched with, not slices, wide moves or rotations
+    UnsupportedMove(Rotation),
+    DepthLimit { depth: usize },
+    NodeLimit { nodes: usize },
+    MemoryLimit { bytes: usize },
+    Timeout { elapsed: Duration },
+}
+
+impl SolveError {
+    // There can still be a solution, just not within the limits
+    pub fn is_limit(&self) -> bool {
+        !matches!(self, SolveError::InvalidCube(_) | SolveError::Unsolvable | SolveError::UnsupportedMove(_))
+    }
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveError::InvalidCube(error) => write!(f, "invalid cube: {}", error),
+            SolveError::Unsolvable => write!(f, "no moves turn the start cube into the end cube"),
+            SolveError::UnsupportedMove(rotation) => write!(f, "{} is not a face turn, only face turns can be searched with", rotation),
+            SolveError::DepthLimit { depth } => write!(f, "no solution with at most {} moves", depth),
+            SolveError::NodeLimit { nodes } => write!(f, "stored more than {} cubes", nodes),
+            SolveError::MemoryLimit { bytes } => write!(f, "used more than {} bytes", bytes),
+            SolveError::Timeout { elapsed } => write!(f, "no solution found in {:.1?}", elapsed),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+impl From<ValidationError> for SolveError {
+    fn from(error: ValidationError) -> SolveError {
+        SolveError::InvalidCube(error)
+    }
+}
+
+
+// The cubie cube the moves from start to end solve, for the solvers that
+// search on cubies
+pub(crate) fn relative_cubie(start_cube: &Cube, end_cube: &Cube) -> Result<CubieCube, SolveError> {
+    start_cube.validate()?;
+    end_cube.validate()?;
+    // Face turns never move the centers
+    if (0..6).any(|side| start_cube.get_at(side, 4) != end_cube.get_at(side, 4)) {
+        return Err(SolveError::Unsolvable);
+    }
+    let start = CubieCube::from_cube(start_cube).ok_or(SolveError::Unsolvable)?;
+    let end = CubieCube::from_cube(end_cube).ok_or(SolveError::Unsolvable)?;
+    // The moves that take start to end are the ones that solve this cube
+    Ok(end.inverse().multiply(&start))
+}
+
+
+// Checks the limits of a config while a search runs
+pub(crate) struct SearchLimits<'a> {
+    pub(crate) config: &'a SolverConfig,
+    started: Instant,
+}
+
+impl<'a> SearchLimits<'a> {
+    pub(crate) fn new(config: &'a SolverConfig) -> SearchLimits<'a> {
+        SearchLimits { config, started: Instant::now() }
+    }
+
+    pub(crate) fn check(&self, nodes: usize, bytes: usize) -> Result<(), SolveError> {
+        if let Some(max_nodes) = self.config.max_nodes {
+            if nodes > max_nodes {
+                return Err(SolveError::NodeLimit { nodes: max_nodes })
+            }
+        }
+        if let Some(memory_budget) = self.config.memory_budget {
+            if bytes > memory_budget {
+                return Err(SolveError::MemoryLimit { bytes: memory_budget })
+            }
+        }
+        if let Some(timeout) = self.config.timeout {
+            let elapsed = self.started.elapsed();
+            if elapsed > timeout {
+                return Err(SolveError::Timeout { elapsed })
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::{solve_cube_two_way_breath_first, Cube};
+    use crate::notation::parse_algorithm;
+
+    #[test]
+    fn each_limit_has_its_own_error() {
+        let start_cube = Cube::new().rotate_all(&parse_algorithm("R U F' L D2 B").unwrap());
+        let solve = |config: SolverConfig| solve_cube_two_way_breath_first(&start_cube, &Cube::new(), Metric::HalfTurn, &config);
+        assert_eq!(solve(SolverConfig::new()).unwrap().len(), 6);
+        assert_eq!(solve(SolverConfig::new().with_max_depth(4)), Err(SolveError::DepthLimit { depth: 4 }));
+        assert_eq!(solve(SolverConfig::new().with_max_nodes(1000)), Err(SolveError::NodeLimit { nodes: 1000 }));
+        assert_eq!(solve(SolverConfig::new().with_memory_budget(10000)), Err(SolveError::MemoryLimit { bytes: 10000 }));
+        let timeout = solve(SolverConfig::new().with_timeout(Duration::ZERO));
+        assert!(matches!(timeout, Err(SolveError::Timeout { .. })));
+        assert!(timeout.unwrap_err().is_limit());
+
+        let mut twisted = crate::cubie::CubieCube::new();
+        twisted.corner_orientation[0] = 1;
+        let invalid = solve_cube_two_way_breath_first(&twisted.to_cube(), &Cube::new(), Metric::HalfTurn, &SolverConfig::new());
+        assert_eq!(invalid, Err(SolveError::InvalidCube(ValidationError::CornerTwist { twist: 1 })));
+        assert!(!invalid.unwrap_err().is_limit());
+        let held_differently = Cube::new().rotate(&Rotation::Y);
+        let unsolvable = solve_cube_two_way_breath_first(&held_differently, &Cube::new(), Metric::HalfTurn, &SolverConfig::new());
+        assert_eq!(unsolvable, Err(SolveError::Unsolvable));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::cube::Rotation;
+use crate::cubie::CubieCube;
+
+
+// Where each of the 48 corner and edge stickers goes, corner stickers are
+// 3 * corner + twist and edge stickers 24 + 2 * edge + flip
+type Permutation = [u8; 48];
+
+const IDENTITY: Permutation = {
+    let mut permutation = [0; 48];
+    let mut i = 0;
+    while i < 48 {
+        permutation[i] = i as u8;
+        i += 1;
+    }
+    permutation
+};
+
+fn permutation(cube: &CubieCube) -> Permutation {
+    let mut permutation = [0; 48];
+    for position in 0..8 {
+        let corner = cube.corner_permutation[position] as usize;
+        for twist in 0..3 {
+            permutation[3 * corner + twist] = (3 * position + (twist + cube.corner_orientation[position] as usize) % 3) as u8;
+        }
+    }
+    for position in 0..12 {
+        let edge = cube.edge_permutation[position] as usize;
+        for flip in 0..2 {
+            permutation[24 + 2 * edge + flip] = (24 + 2 * position + (flip + cube.edge_orientation[position] as usize) % 2) as u8;
+        }
+    }
+    permutation
+}
+
+// `first` then `second`
+fn compose(first: &Permutation, second: &Permutation) -> Permutation {
+    let mut result = [0; 48];
+    for (point, image) in result.iter_mut().enumerate() {
+        *image = second[first[point] as usize];
+    }
+    result
+}
+
+fn invert(permutation: &Permutation) -> Permutation {
+    let mut result = [0; 48];
+    for (point, image) in permutation.iter().enumerate() {
+        result[*image as usize] = point as u8;
+    }
+    result
+}
+
+
+// The group the moves of a restricted move set generate, e.g. <R, U>, as a
+// chain of stabilizers (Knuth's version of the Schreier-Sims algorithm).
+// Tells whether a cube can be solved with the moves at all without searching.
+pub struct Subgroup {
+    // Elements added for each point, they leave the points before it alone
+    generators: Vec<Vec<Permutation>>,
+    // For each point an element of the group fixing the points before it
+    // that moves the point to each place it can go, by that place
+    transversals: Vec<Vec<Option<Permutation>>>,
+}
+
+impl Subgroup {
+    // Only for face turns
+    pub fn new(rotations: &[Rotation]) -> Subgroup {
+        let mut subgroup = Subgroup {
+            generators: vec![vec![]; 48],
+            transversals: (0..48).map(|point| {
+                let mut transversal = vec![None; 48];
+                transversal[point] = Some(IDENTITY);
+                transversal
+            }).collect(),
+        };
+        for rotation in rotations {
+            subgroup.add(permutation(&CubieCube::new().rotate(rotation)), 0);
+        }
+        subgroup
+    }
+
+    // Whether the element is in the group, for an element fixing the points
+    // before `point`
+    fn contains_from(&self, mut element: Permutation, point: usize) -> bool {
+        for point in point..48 {
+            match &self.transversals[point][element[point] as usize] {
+                Some(to_image) => element = compose(&element, &invert(to_image)),
+                None => return false,
+            }
+        }
+        element == IDENTITY
+    }
+
+    fn add(&mut self, element: Permutation, point: usize) {
+        if self.contains_from(element, point) {
+            return
+        }
+        self.generators[point].push(element);
+        let transversal: Vec<Permutation> = self.transversals[point].iter().flatten().cloned().collect();
+        for to_image in transversal {
+            self.extend(compose(&to_image, &element), point);
+        }
+    }
+
+    // Adds where the element moves the point to the transversal, or when it
+    // is there already what is left of the element to the next point
+    fn extend(&mut self, element: Permutation, point: usize) {
+        let image = element[point] as usize;
+        match self.transversals[point][image] {
+            Some(to_image) => self.add(compose(&element, &invert(&to_image)), point + 1),
+            None => {
+                self.transversals[point][image] = Some(element);
+                for generator in self.generators[point].clone() {
+                    self.extend(compose(&element, &generator), point);
+                }
+            },
+        }
+    }
+
+    // Number of cubes the moves can reach from a cube
+    pub fn order(&self) -> u128 {
+        self.transversals.iter().map(|transversal| transversal.iter().flatten().count() as u128).product()
+    }
+
+    // Whether the moves turn the cube `from` into `to`
+    pub fn connects(&self, from: &CubieCube, to: &CubieCube) -> bool {
+        self.contains_from(permutation(&from.inverse().multiply(to)), 0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::{solve_cube_two_way_breath_first, Cube, Metric};
+    use crate::notation::parse_algorithm;
+    use crate::solver::{SolveError, SolverConfig};
+
+    fn order(moves: &str) -> u128 {
+        Subgroup::new(&parse_algorithm(moves).unwrap()).order()
+    }
+
+    #[test]
+    fn knows_the_order_of_subgroups() {
+        assert_eq!(order("U D R L F B"), 43252003274489856000);
+        assert_eq!(order("R U"), 73483200);
+        assert_eq!(order("U D R2 L2 F2 B2"), 19508428800);
+        assert_eq!(order("R2 U2"), 12);
+        assert_eq!(order("U"), 4);
+    }
+
+    #[test]
+    fn tells_which_cubes_the_moves_solve() {
+        let subgroup = Subgroup::new(&[Rotation::R, Rotation::U]);
+        let solved = CubieCube::new();
+        assert!(subgroup.connects(&solved.rotate_all(&parse_algorithm("R U' R2 U2 R'").unwrap()), &solved));
+        assert!(!subgroup.connects(&solved.rotate(&Rotation::F), &solved));
+
+        let config = SolverConfig::new().with_moves(&[Rotation::R, Rotation::U]);
+        let start_cube = Cube::new().rotate_all(&parse_algorithm("R U' R2 U2 R'").unwrap());
+        let solution = solve_cube_two_way_breath_first(&start_cube, &Cube::new(), Metric::HalfTurn, &config).unwrap();
+        assert_eq!(start_cube.rotate_all(&solution), Cube::new());
+        assert!(solution.iter().all(|rotation| rotation.to_string().starts_with(['R', 'U'])));
+        let start_cube = Cube::new().rotate(&Rotation::F);
+        assert_eq!(
+            solve_cube_two_way_breath_first(&start_cube, &Cube::new(), Metric::HalfTurn, &config),
+            Err(SolveError::Unsolvable),
+        );
+    }
+
+    #[test]
+    fn refuses_moves_that_are_no_face_turns() {
+        for rotation in [Rotation::M, Rotation::Rw, Rotation::X] {
+            let config = SolverConfig::new().with_moves(&[Rotation::U, rotation.clone()]);
+            assert_eq!(config.rotations(Metric::HalfTurn), Err(SolveError::UnsupportedMove(rotation.clone())));
+            let start_cube = Cube::new().rotate(&Rotation::U);
+            assert_eq!(
+                solve_cube_two_way_breath_first(&start_cube, &Cube::new(), Metric::HalfTurn, &config),
+                Err(SolveError::UnsupportedMove(rotation)),
+            );
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use crate::cube::{Cube, Key, Metric, Rotation};
+use crate::cubie::CubieCube;
+use crate::facelets::FACELETS;
+
+
+pub const SYMMETRY_COUNT: usize = 48;
+
+// A whole cube rotation, possibly followed by mirroring left and right.
+// Stickers are numbered side * 9 + face.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symmetry {
+    // Where the sticker at each position is moved to
+    pub positions: [u8; 54],
+    // Where the sticker moved to each position comes from
+    sources: [u8; 54],
+    // The side each color is moved to, so centers keep their colors
+    pub colors: [u8; 6],
+    pub mirrored: bool,
+}
+
+impl Symmetry {
+    // Built from a cube whose stickers are numbered by the position they
+    // started at
+    fn from_labels(labels: &Cube, mirrored: bool) -> Symmetry {
+        let mut positions = [0; 54];
+        let mut sources = [0; 54];
+        for (position, source) in sources.iter_mut().enumerate() {
+            *source = labels.get_at(position / 9, position % 9);
+            positions[*source as usize] = position as u8;
+        }
+        let mut colors = [0; 6];
+        for (side, color) in colors.iter_mut().enumerate() {
+            *color = positions[side * 9 + 4] / 9;
+        }
+        Symmetry { positions, sources, colors, mirrored }
+    }
+}
+
+
+// Positions in the order get_key reads them, the stickers before the centers,
+// so comparing colors in this order compares keys
+const KEY_ORDER: [usize; 54] = {
+    let mut order = [0; 54];
+    let mut position = 0;
+    while position < 54 {
+        let index = if position % 9 == 4 {
+            48 + position / 9
+        } else {
+            position - position / 9 - (position % 9 > 4) as usize
+        };
+        order[index] = position;
+        position += 1;
+    }
+    order
+};
+
+
+// Every sticker numbered by its own position
+fn position_labels() -> Cube {
+    let mut cube = Cube::new();
+    for position in 0..54 {
+        cube.set_at(position / 9, position % 9, position as u8);
+    }
+    cube
+}
+
+// Mirrors left and right. In the facelet string layout every face is only
+// flipped horizontally, with R and L trading places.
+fn mirror_labels(labels: &Cube) -> Cube {
+    let mut mirrored = labels.clone();
+    for (index, (side, face)) in FACELETS.iter().enumerate() {
+        let face_name = index / 9;
+        let mirrored_face_name = match face_name {
+            1 => 4,
+            4 => 1,
+            other => other,
+        };
+        let (row, column) = (index % 9 / 3, index % 3);
+        let (mirrored_side, mirrored_face) = FACELETS[mirrored_face_name * 9 + row * 3 + 2 - column];
+        mirrored.set_at(mirrored_side, mirrored_face, labels.get_at(*side, *face));
+    }
+    mirrored
+}
+
+// The 24 whole cube rotations followed by the same 24 mirrored, the identity first
+pub fn symmetries() -> &'static [Symmetry] {
+    static SYMMETRIES: OnceLock<Vec<Symmetry>> = OnceLock::new();
+    SYMMETRIES.get_or_init(|| {
+        let mut rotations = vec![position_labels()];
+        let mut seen: HashSet<Cube> = rotations.iter().cloned().collect();
+        let mut i = 0;
+        while i < rotations.len() {
+            for rotation in [Rotation::X, Rotation::Y] {
+                let rotated = rotations[i].rotate(&rotation);
+                if seen.insert(rotated.clone()) {
+                    rotations.push(rotated);
+                }
+            }
+            i += 1;
+        }
+        let mut symmetries: Vec<Symmetry> = rotations.iter().map(|labels| Symmetry::from_labels(labels, false)).collect();
+        symmetries.extend(rotations.iter().map(|labels| Symmetry::from_labels(&mirror_labels(labels), true)));
+        symmetries
+    })
+}
+
+// The symmetry of a whole cube rotation, e.g. Rotation::X2
+pub fn whole_cube_symmetry(rotation: &Rotation) -> Symmetry {
+    Symmetry::from_labels(&position_labels().rotate(rotation), false)
+}
+
+
+impl Cube {
+    // The cube moved by the symmetry and recolored so the centers keep their
+    // colors. Turning a conjugated cube with `conjugate_rotation` gives the
+    // conjugate of the turned cube, and the solved cube is its own conjugate.
+    pub fn conjugate(&self, symmetry: &Symmetry) -> Cube {
+        let mut cube = self.clone();
+        for position in 0..54 {
+            let color = self.get_at(position / 9, position % 9);
+            let target = symmetry.positions[position] as usize;
+            cube.set_at(target / 9, target % 9, symmetry.colors[color as usize]);
+        }
+        cube
+    }
+
+    // The symmetries that leave the cube as it is, all 48 for the solved cube
+    pub fn symmetries(&self) -> Vec<&'static Symmetry> {
+        symmetries().iter().filter(|symmetry| self.conjugate(symmetry) == *self).collect()
+    }
+
+    // The same key for all cubes the symmetries turn into each other, the
+    // smallest key of the conjugates. `symmetries` has to be closed under
+    // composition, like the symmetries of a cube.
+    // The conjugates are never built, their colors are read through the
+    // tables of the symmetries in key order and a conjugate is dropped at the
+    // first color that makes its key larger than the smallest so far.
+    pub(crate) fn symmetry_key(&self, symmetries: &[&Symmetry]) -> Key {
+        if symmetries.is_empty() {
+            return self.get_key()
+        }
+        let stickers: [u8; 54] = std::array::from_fn(|position| self.get_at(position / 9, position % 9));
+        let color = |symmetry: &Symmetry, position: usize| {
+            symmetry.colors[stickers[symmetry.sources[position] as usize] as usize]
+        };
+        let mut smallest = symmetries[0];
+        for symmetry in symmetries[1..].iter() {
+            for position in KEY_ORDER {
+                let (color, smallest_color) = (color(symmetry, position), color(smallest, position));
+                if color != smallest_color {
+                    if color < smallest_color {
+                        smallest = symmetry;
+                    }
+                    break;
+                }
+            }
+        }
+        let mut stickers_key: u128 = 0;
+        let mut centers_key: u16 = 0;
+        for position in KEY_ORDER {
+            let color = color(smallest, position);
+            if position % 9 == 4 {
+                centers_key = centers_key * 6 + color as u16;
+            } else {
+                stickers_key = stickers_key * 6 + color as u128;
+            }
+        }
+        (stickers_key, centers_key)
+    }
+}
+
+
+impl CubieCube {
+    pub fn conjugate(&self, symmetry: &Symmetry) -> CubieCube {
+        CubieCube::from_cube(&self.to_cube().conjugate(symmetry)).unwrap()
+    }
+}
+
+
+// The face turn that does to a conjugated cube what the face turn `rotation`
+// does to the cube, e.g. mirroring turns R into L'
+pub fn conjugate_rotation(rotation: &Rotation, symmetry: &Symmetry) -> Rotation {
+    let turned = Cube::new().rotate(rotation).conjugate(symmetry);
+    match Metric::HalfTurn.rotations().into_iter().find(|other| Cube::new().rotate(other) == turned) {
+        Some(other) => other,
+        None => panic!("{} is not a face turn", rotation),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_algorithm;
+
+    #[test]
+    fn there_are_48_symmetries() {
+        assert_eq!(symmetries().len(), SYMMETRY_COUNT);
+        assert_eq!(Cube::new().symmetries().len(), SYMMETRY_COUNT);
+        let distinct: HashSet<[u8; 54]> = symmetries().iter().map(|symmetry| symmetry.positions).collect();
+        assert_eq!(distinct.len(), SYMMETRY_COUNT);
+        assert_eq!(conjugate_rotation(&Rotation::R, &symmetries()[24]), Rotation::Lr);
+    }
+
+    #[test]
+    fn conjugates_share_a_key() {
+        let all: Vec<&Symmetry> = symmetries().iter().collect();
+        let cube = Cube::new().rotate_all(&parse_algorithm("R U2 F' D L B'").unwrap());
+        let key = cube.symmetry_key(&all);
+        for symmetry in all.iter() {
+            let conjugate = cube.conjugate(symmetry);
+            assert_eq!(conjugate.symmetry_key(&all), key);
+            // The smallest key of the conjugates, as if they were all built
+            assert!(conjugate.get_key() >= key);
+        }
+        assert!(all.iter().any(|symmetry| cube.conjugate(symmetry).get_key() == key));
+        let other = Cube::new().rotate_all(&parse_algorithm("R U2 F' D L B").unwrap());
+        assert_ne!(other.symmetry_key(&all), key);
+        assert_eq!(cube.symmetry_key(&[]), cube.get_key());
+    }
+}
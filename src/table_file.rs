@@ -0,0 +1,279 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::cube::Metric;
+use crate::pattern_database::NibbleTable;
+
+
+// A table file is a header followed by the bytes of its tables:
+//   magic        8 bytes  "RBXTABLE"
+//   version      u32
+//   puzzle       8 bytes  "3x3x3", zero padded
+//   kind         u8 length and that many bytes, e.g. "korf"
+//   metric       u8       0 quarter turn, 1 half turn
+//   table count  u32 and the byte length of each table as u64
+//   checksum     u64      FNV-1a over all table bytes
+// Numbers are little endian.
+const MAGIC: &[u8; 8] = b"RBXTABLE";
+pub const TABLE_FORMAT_VERSION: u32 = 1;
+const PUZZLE: &[u8; 8] = b"3x3x3\0\0\0";
+
+pub const DEFAULT_TABLE_DIRECTORY: &str = "tables";
+
+
+#[derive(Debug)]
+pub enum TableError {
+    Io(io::Error),
+    NotATableFile,
+    UnsupportedVersion(u32),
+    WrongPuzzle,
+    WrongKind { expected: String, found: String },
+    WrongMetric { expected: Metric, found: Metric },
+    WrongSize,
+    ChecksumMismatch,
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TableError::Io(error) => write!(f, "{}", error),
+            TableError::NotATableFile => write!(f, "not a table file"),
+            TableError::UnsupportedVersion(version) => {
+                write!(f, "table format version {} is not supported, expected {}", version, TABLE_FORMAT_VERSION)
+            },
+            TableError::WrongPuzzle => write!(f, "tables are for another puzzle"),
+            TableError::WrongKind { expected, found } => write!(f, "expected {} tables, found {} tables", expected, found),
+            TableError::WrongMetric { expected, found } => write!(f, "expected tables for {:?}, found {:?}", expected, found),
+            TableError::WrongSize => write!(f, "tables have the wrong size"),
+            TableError::ChecksumMismatch => write!(f, "checksum does not match, the file is damaged"),
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+impl From<io::Error> for TableError {
+    fn from(error: io::Error) -> TableError {
+        TableError::Io(error)
+    }
+}
+
+
+fn checksum(tables: &[&NibbleTable]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for table in tables.iter() {
+        for byte in table.data.iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+fn metric_id(metric: Metric) -> u8 {
+    match metric {
+        Metric::QuarterTurn => 0,
+        Metric::HalfTurn => 1,
+    }
+}
+
+// The directory tables are kept in, changed with the RUBICS_TABLES
+// environment variable
+pub fn table_directory() -> PathBuf {
+    PathBuf::from(std::env::var("RUBICS_TABLES").unwrap_or(DEFAULT_TABLE_DIRECTORY.to_string()))
+}
+
+// e.g. korf-htm.tbl
+pub fn table_file_name(kind: &str, metric: Metric) -> String {
+    let metric_name = match metric {
+        Metric::QuarterTurn => "qtm",
+        Metric::HalfTurn => "htm",
+    };
+    format!("{}-{}.tbl", kind, metric_name)
+}
+
+pub fn table_path(kind: &str, metric: Metric) -> PathBuf {
+    table_directory().join(table_file_name(kind, metric))
+}
+
+pub(crate) fn write_tables(path: &Path, kind: &str, metric: Metric, tables: &[&NibbleTable]) -> Result<(), TableError> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&TABLE_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(PUZZLE);
+    bytes.push(kind.len() as u8);
+    bytes.extend_from_slice(kind.as_bytes());
+    bytes.push(metric_id(metric));
+    bytes.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+    for table in tables.iter() {
+        bytes.extend_from_slice(&(table.data.len() as u64).to_le_bytes());
+    }
+    bytes.extend_from_slice(&checksum(tables).to_le_bytes());
+    for table in tables.iter() {
+        bytes.extend_from_slice(&table.data);
+    }
+
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+// Reads tables written by write_tables and checks they are the tables asked
+// for, `lens` being the number of entries of each table
+pub(crate) fn read_tables(path: &Path, kind: &str, metric: Metric, lens: &[usize]) -> Result<Vec<NibbleTable>, TableError> {
+    let bytes = fs::read(path)?;
+    let mut reader = Reader { bytes: &bytes, index: 0 };
+
+    if reader.take(8)? != MAGIC {
+        return Err(TableError::NotATableFile);
+    }
+    let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+    if version != TABLE_FORMAT_VERSION {
+        return Err(TableError::UnsupportedVersion(version));
+    }
+    if reader.take(8)? != PUZZLE {
+        return Err(TableError::WrongPuzzle);
+    }
+    let kind_length = reader.take(1)?[0] as usize;
+    let found_kind = String::from_utf8_lossy(reader.take(kind_length)?).to_string();
+    if found_kind != kind {
+        return Err(TableError::WrongKind { expected: kind.to_string(), found: found_kind });
+    }
+    let found_metric = match reader.take(1)?[0] {
+        0 => Metric::QuarterTurn,
+        1 => Metric::HalfTurn,
+        _ => return Err(TableError::NotATableFile),
+    };
+    if found_metric != metric {
+        return Err(TableError::WrongMetric { expected: metric, found: found_metric });
+    }
+    let count = u32::from_le_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+    if count != lens.len() {
+        return Err(TableError::WrongSize);
+    }
+    for len in lens.iter() {
+        let byte_length = u64::from_le_bytes(reader.take(8)?.try_into().unwrap()) as usize;
+        if byte_length != NibbleTable::new(*len).data.len() {
+            return Err(TableError::WrongSize);
+        }
+    }
+    let expected_checksum = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+
+    let mut tables = vec![];
+    for len in lens.iter() {
+        let mut table = NibbleTable::new(*len);
+        let byte_length = table.data.len();
+        table.data.copy_from_slice(reader.take(byte_length)?);
+        tables.push(table);
+    }
+    if reader.index != bytes.len() {
+        return Err(TableError::WrongSize);
+    }
+    if checksum(&tables.iter().collect::<Vec<&NibbleTable>>()) != expected_checksum {
+        return Err(TableError::ChecksumMismatch);
+    }
+    Ok(tables)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], TableError> {
+        if self.index + length > self.bytes.len() {
+            return Err(TableError::WrongSize);
+        }
+        self.index += length;
+        Ok(&self.bytes[self.index - length..self.index])
+    }
+}
+
+// Where the tables a solver uses came from
+#[derive(Debug)]
+pub enum TableSource {
+    Loaded(PathBuf),
+    // There was no file
+    Built,
+    // The file was refused for the error and the tables were built instead
+    Rebuilt(PathBuf, TableError),
+}
+
+// Loads the tables from their file if there is one, builds them otherwise.
+// A file that does not match is refused and the tables are built instead,
+// the source tells the caller so it can say why.
+pub(crate) fn load_or_build<T>(
+    kind: &str,
+    metric: Metric,
+    load: impl Fn(&Path, Metric) -> Result<T, TableError>,
+    build: impl Fn(Metric) -> T,
+) -> (T, TableSource) {
+    let path = table_path(kind, metric);
+    if !path.exists() {
+        return (build(metric), TableSource::Built)
+    }
+    match load(&path, metric) {
+        Ok(tables) => (tables, TableSource::Loaded(path)),
+        Err(error) => (build(metric), TableSource::Rebuilt(path, error)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables() -> Vec<NibbleTable> {
+        let mut tables = vec![NibbleTable::new(5), NibbleTable::new(100)];
+        for (index, table) in tables.iter_mut().enumerate() {
+            table.set(index, 3);
+        }
+        tables
+    }
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rubics-solver-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn tables_round_trip() {
+        let path = path("round-trip.tbl");
+        write_tables(&path, "test", Metric::HalfTurn, &tables().iter().collect::<Vec<_>>()).unwrap();
+        let read = read_tables(&path, "test", Metric::HalfTurn, &[5, 100]).unwrap();
+        for (read, written) in read.iter().zip(tables().iter()) {
+            assert_eq!(read.data, written.data);
+        }
+
+        assert!(matches!(read_tables(&path, "korf", Metric::HalfTurn, &[5, 100]), Err(TableError::WrongKind { .. })));
+        assert!(matches!(read_tables(&path, "test", Metric::QuarterTurn, &[5, 100]), Err(TableError::WrongMetric { .. })));
+        assert!(matches!(read_tables(&path, "test", Metric::HalfTurn, &[5, 101]), Err(TableError::WrongSize)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuses_damaged_files() {
+        let path = path("damaged.tbl");
+        write_tables(&path, "test", Metric::HalfTurn, &tables().iter().collect::<Vec<_>>()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let read = |bytes: &[u8]| {
+            fs::write(&path, bytes).unwrap();
+            read_tables(&path, "test", Metric::HalfTurn, &[5, 100])
+        };
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(matches!(read(&magic), Err(TableError::NotATableFile)));
+        let mut version = bytes.clone();
+        version[8] = 2;
+        assert!(matches!(read(&version), Err(TableError::UnsupportedVersion(2))));
+        let mut data = bytes.clone();
+        *data.last_mut().unwrap() ^= 1;
+        assert!(matches!(read(&data), Err(TableError::ChecksumMismatch)));
+        assert!(matches!(read(&bytes[..bytes.len() - 1]), Err(TableError::WrongSize)));
+        fs::remove_file(&path).unwrap();
+    }
+}
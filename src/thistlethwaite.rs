@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::Path;
+use std::sync::OnceLock;
+use crate::coordinates::{binomial, permutation_rank};
+use crate::cube::{Cube, Metric, Rotation};
+use crate::cubie::CubieCube;
+use crate::pattern_database::NibbleTable;
+use crate::solver::{relative_cubie, SolveError, SolverConfig};
+use crate::table_file::{load_or_build, read_tables, write_tables, TableError, TableSource};
+use crate::two_phase::{move_table, PHASE_2_MOVES, SOLVED_SLICE};
+
+
+pub const THISTLETHWAITE_KIND: &str = "thistlethwaite";
+
+pub const THISTLETHWAITE_PHASES: [&str; 4] = [
+    "G0 -> G1 <U, D, R, L, F2, B2>",
+    "G1 -> G2 <U, D, R2, L2, F2, B2>",
+    "G2 -> G3 <U2, D2, R2, L2, F2, B2>",
+    "G3 -> solved",
+];
+
+// Moves of each phase as indices into Metric::HalfTurn.rotations(), every
+// phase only uses moves of the group it starts in
+const PHASE_1_MOVES: [usize; 18] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17];
+const PHASE_2_MOVES_G1: [usize; 14] = [0, 1, 2, 3, 6, 7, 8, 9, 12, 13, 14, 15, 16, 17];
+const PHASE_4_MOVES: [usize; 6] = [12, 13, 14, 15, 16, 17];
+
+// In G2 the UF, UB, DF and DB edges are somewhere in the U and D layers (the
+// first eight positions). Which four of those positions they are at, 0..70.
+fn m_slice(cube: &CubieCube) -> u16 {
+    let mut m_slice = 0;
+    let mut found = 0;
+    for position in 0..8 {
+        if cube.edge_permutation[position] % 2 == 1 && cube.edge_permutation[position] < 8 {
+            found += 1;
+            m_slice += binomial(position, found);
+        }
+    }
+    m_slice as u16
+}
+
+fn set_m_slice(cube: &mut CubieCube, m_slice: u16) {
+    let mut m_slice = m_slice as usize;
+    let mut positions = [false; 8];
+    for found in (1..5).rev() {
+        let mut position = found - 1;
+        while binomial(position + 1, found) <= m_slice {
+            position += 1;
+        }
+        m_slice -= binomial(position, found);
+        positions[position] = true;
+    }
+    let mut m_edge = 1;
+    let mut other_edge = 0;
+    for (position, is_m) in positions.iter().enumerate() {
+        if *is_m {
+            cube.edge_permutation[position] = m_edge;
+            m_edge += 2;
+        } else {
+            cube.edge_permutation[position] = other_edge;
+            other_edge += 2;
+        }
+    }
+    cube.edge_permutation[8..].copy_from_slice(&[8, 9, 10, 11]);
+}
+
+// UF, UB, DF and DB at their own positions
+const SOLVED_M_SLICE: usize = 49;
+
+
+// Exact distances to the next group for every phase, so each phase is solved
+// in as few moves as its generators allow
+pub struct ThistlethwaiteTables {
+    rotations: Vec<Rotation>,
+    flip_moves: Vec<u16>,
+    flip_distances: NibbleTable,
+    twist_moves: Vec<u16>,
+    slice_moves: Vec<u16>,
+    twist_slice_distances: NibbleTable,
+    corner_permutation_moves: Vec<u16>,
+    m_slice_moves: Vec<u16>,
+    corner_m_slice_distances: NibbleTable,
+    // G3 is small enough to keep every state with its distance to solved
+    g3_distances: HashMap<(u16, u32), u8>,
+}
+
+fn g3_key(cube: &CubieCube) -> (u16, u32) {
+    (cube.corner_permutation_rank(), permutation_rank(&cube.edge_permutation) as u32)
+}
+
+impl ThistlethwaiteTables {
+    fn with_move_tables() -> ThistlethwaiteTables {
+        let rotations = Metric::HalfTurn.rotations();
+        let cubies: Vec<CubieCube> = rotations.iter().map(|rotation| CubieCube::new().rotate(rotation)).collect();
+        let phase_moves = |moves: &[usize]| -> Vec<CubieCube> {
+            moves.iter().map(|rotation| cubies[*rotation].clone()).collect()
+        };
+        let phase_1_moves = phase_moves(&PHASE_1_MOVES);
+        let phase_2_moves = phase_moves(&PHASE_2_MOVES_G1);
+        let phase_3_moves = phase_moves(&PHASE_2_MOVES);
+        let phase_4_moves = phase_moves(&PHASE_4_MOVES);
+
+        let mut g3 = vec![CubieCube::new()];
+        let mut g3_distances = HashMap::new();
+        g3_distances.insert(g3_key(&g3[0]), 0);
+        let mut depth = 0;
+        while !g3.is_empty() {
+            depth += 1;
+            let mut next = vec![];
+            for cube in g3.iter() {
+                for rotation in phase_4_moves.iter() {
+                    let rotated_cube = cube.multiply(rotation);
+                    let key = g3_key(&rotated_cube);
+                    if let Entry::Vacant(entry) = g3_distances.entry(key) {
+                        entry.insert(depth);
+                        next.push(rotated_cube);
+                    }
+                }
+            }
+            g3 = next;
+        }
+
+        ThistlethwaiteTables {
+            rotations,
+            flip_moves: move_table(2048, &phase_1_moves, CubieCube::set_flip, CubieCube::flip),
+            flip_distances: NibbleTable::new(0),
+            twist_moves: move_table(2187, &phase_2_moves, CubieCube::set_twist, CubieCube::twist),
+            slice_moves: move_table(495, &phase_2_moves, CubieCube::set_slice, CubieCube::slice),
+            twist_slice_distances: NibbleTable::new(0),
+            corner_permutation_moves: move_table(
+                40320, &phase_3_moves, CubieCube::set_corner_permutation_rank, CubieCube::corner_permutation_rank,
+            ),
+            m_slice_moves: move_table(70, &phase_3_moves, set_m_slice, m_slice),
+            corner_m_slice_distances: NibbleTable::new(0),
+            g3_distances,
+        }
+    }
+
+    pub fn new() -> ThistlethwaiteTables {
+        let mut tables = ThistlethwaiteTables::with_move_tables();
+        tables.flip_distances = NibbleTable::new(2048);
+        tables.flip_distances.fill(2048, &[0], &[1; 18], |index, rotation| tables.flip_moves[index * 18 + rotation] as usize);
+
+        tables.twist_slice_distances = NibbleTable::new(495 * 2187);
+        tables.twist_slice_distances.fill(495 * 2187, &[SOLVED_SLICE * 2187], &[1; 14], |index, rotation| {
+            tables.slice_moves[index / 2187 * 14 + rotation] as usize * 2187 + tables.twist_moves[index % 2187 * 14 + rotation] as usize
+        });
+
+        // G3 allows the 96 corner permutations half turns can make
+        let mut g3_corners: Vec<usize> = tables.g3_distances.keys().map(|(corners, _)| *corners as usize * 70 + SOLVED_M_SLICE).collect();
+        g3_corners.sort();
+        g3_corners.dedup();
+        tables.corner_m_slice_distances = NibbleTable::new(40320 * 70);
+        tables.corner_m_slice_distances.fill(40320 * 70, &g3_corners, &[1; 10], |index, rotation| {
+            tables.corner_permutation_moves[index / 70 * 10 + rotation] as usize * 70 + tables.m_slice_moves[index % 70 * 10 + rotation] as usize
+        });
+        tables
+    }
+
+    // The distances in G3 are rebuilt, that only takes a fraction of a second
+    pub fn load(path: &Path) -> Result<ThistlethwaiteTables, TableError> {
+        let mut tables = ThistlethwaiteTables::with_move_tables();
+        let lens = [2048, 495 * 2187, 40320 * 70];
+        let mut distances = read_tables(path, THISTLETHWAITE_KIND, Metric::HalfTurn, &lens)?.into_iter();
+        tables.flip_distances = distances.next().unwrap();
+        tables.twist_slice_distances = distances.next().unwrap();
+        tables.corner_m_slice_distances = distances.next().unwrap();
+        Ok(tables)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), TableError> {
+        let distances = [&self.flip_distances, &self.twist_slice_distances, &self.corner_m_slice_distances];
+        write_tables(path, THISTLETHWAITE_KIND, Metric::HalfTurn, &distances)
+    }
+}
+
+impl Default for ThistlethwaiteTables {
+    fn default() -> ThistlethwaiteTables {
+        ThistlethwaiteTables::new()
+    }
+}
+
+// Follows the distances down to 0, returns the moves taken as indices into `moves`
+fn descend(table: &NibbleTable, mut index: usize, moves: usize, next: impl Fn(usize, usize) -> usize) -> Vec<usize> {
+    let mut path = vec![];
+    while table.get(index) > 0 {
+        let distance = table.get(index);
+        let rotation = (0..moves).find(|rotation| table.get(next(index, *rotation)) == distance - 1).unwrap();
+        path.push(rotation);
+        index = next(index, rotation);
+    }
+    path
+}
+
+// The tables solve_cube_thistlethwaite uses and where they came from, loaded
+// or built on first use
+pub fn thistlethwaite_tables() -> &'static (ThistlethwaiteTables, TableSource) {
+    static TABLES: OnceLock<(ThistlethwaiteTables, TableSource)> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        load_or_build(
+            THISTLETHWAITE_KIND,
+            Metric::HalfTurn,
+            |path, _| ThistlethwaiteTables::load(path),
+            |_| ThistlethwaiteTables::new(),
+        )
+    })
+}
+
+
+// Thistlethwaite's algorithm: four phases, each restricting the cube to a
+// smaller group until it is solved. Every phase is solved optimally for its
+// moves, which gives at most 7 + 10 + 13 + 15 = 45 moves and usually about 31.
+// Returns the moves of each phase, named in THISTLETHWAITE_PHASES. Every phase
+// only follows its table down, so there is nothing for the limits of the
+// config to stop and the solution is as long as it is whatever max_depth is,
+// only `verbose` is used to print the length of every phase.
+pub fn solve_cube_thistlethwaite(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    config: &SolverConfig,
+) -> Result<Vec<Vec<Rotation>>, SolveError> {
+    let mut cube = relative_cubie(start_cube, end_cube)?;
+    let tables = &thistlethwaite_tables().0;
+    let mut phases: Vec<Vec<Rotation>> = vec![];
+
+    let phase_1 = descend(&tables.flip_distances, cube.flip() as usize, 18, |index, rotation| {
+        tables.flip_moves[index * 18 + rotation] as usize
+    });
+    phases.push(phase_1.iter().map(|rotation| tables.rotations[PHASE_1_MOVES[*rotation]].clone()).collect());
+    cube = cube.rotate_all(&phases[0]);
+
+    let phase_2 = descend(&tables.twist_slice_distances, cube.slice() as usize * 2187 + cube.twist() as usize, 14, |index, rotation| {
+        tables.slice_moves[index / 2187 * 14 + rotation] as usize * 2187 + tables.twist_moves[index % 2187 * 14 + rotation] as usize
+    });
+    phases.push(phase_2.iter().map(|rotation| tables.rotations[PHASE_2_MOVES_G1[*rotation]].clone()).collect());
+    cube = cube.rotate_all(&phases[1]);
+
+    let phase_3 = descend(&tables.corner_m_slice_distances, cube.corner_permutation_rank() as usize * 70 + m_slice(&cube) as usize, 10, |index, rotation| {
+        tables.corner_permutation_moves[index / 70 * 10 + rotation] as usize * 70 + tables.m_slice_moves[index % 70 * 10 + rotation] as usize
+    });
+    phases.push(phase_3.iter().map(|rotation| tables.rotations[PHASE_2_MOVES[*rotation]].clone()).collect());
+    cube = cube.rotate_all(&phases[2]);
+
+    let mut phase_4 = vec![];
+    let mut distance = *tables.g3_distances.get(&g3_key(&cube)).ok_or(SolveError::Unsolvable)?;
+    while distance > 0 {
+        let rotation = PHASE_4_MOVES.iter()
+            .map(|rotation| &tables.rotations[*rotation])
+            .find(|rotation| tables.g3_distances.get(&g3_key(&cube.rotate(rotation))) == Some(&(distance - 1)))
+            .unwrap();
+        cube = cube.rotate(rotation);
+        phase_4.push(rotation.clone());
+        distance -= 1;
+    }
+    phases.push(phase_4);
+
+    if config.verbose {
+        for (name, phase) in THISTLETHWAITE_PHASES.iter().zip(phases.iter()) {
+            println!("{}: {} moves", name, phase.len());
+        }
+    }
+    Ok(phases)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_algorithm;
+
+    #[test]
+    fn every_phase_ends_in_its_group() {
+        let scramble = parse_algorithm("R U2 F' D L2 B R' U F2 D' L B2 U R F' D2 L' B U2 R2").unwrap();
+        let start_cube = Cube::new().rotate_all(&scramble);
+        let phases = solve_cube_thistlethwaite(&start_cube, &Cube::new(), &SolverConfig::new()).unwrap();
+        assert_eq!(phases.len(), THISTLETHWAITE_PHASES.len());
+        assert!(phases.iter().map(|phase| phase.len()).sum::<usize>() <= 45);
+
+        let mut cube = CubieCube::from_cube(&start_cube).unwrap().rotate_all(&phases[0]);
+        assert_eq!(cube.flip(), 0);
+        cube = cube.rotate_all(&phases[1]);
+        assert_eq!((cube.twist(), cube.slice() as usize), (0, SOLVED_SLICE));
+        cube = cube.rotate_all(&phases[2]);
+        let tables = &thistlethwaite_tables().0;
+        assert!(tables.g3_distances.contains_key(&g3_key(&cube)));
+        assert_eq!(cube.rotate_all(&phases[3]), CubieCube::new());
+        // Later phases only use the moves of the group they start in
+        assert!(phases[3].iter().all(|rotation| PHASE_4_MOVES.iter().any(|other| tables.rotations[*other] == *rotation)));
+    }
+
+    #[test]
+    fn refuses_cubes_held_differently() {
+        let held_differently = Cube::new().rotate(&Rotation::Y);
+        assert_eq!(
+            solve_cube_thistlethwaite(&held_differently, &Cube::new(), &SolverConfig::new()),
+            Err(SolveError::Unsolvable),
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::cube::Rotation;
+use crate::simplify::{compose, decompose};
+
+
+// Faces are numbered by axis like the layers in simplify: U, D, R, L, F, B
+const U: usize = 0;
+const D: usize = 1;
+const R: usize = 2;
+const L: usize = 3;
+const F: usize = 4;
+const B: usize = 5;
+
+// The face each layer of an axis turns like: both faces, the slice (E like D,
+// M like L, S like F), both wide moves and the whole cube (y like U, x like R
+// and z like F)
+fn followed_face(axis: usize, layer: usize) -> usize {
+    match layer {
+        0 | 3 | 5 => axis * 2,
+        1 | 4 => axis * 2 + 1,
+        2 if axis == 2 => F,
+        2 => axis * 2 + 1,
+        _ => panic!("Invalid layer {}", layer),
+    }
+}
+
+// Moves every layer onto the layer of the face it is mapped to. Mirroring
+// also turns every move the other way.
+fn map_faces(rotations: &[Rotation], face_map: &[usize; 6], mirror: bool) -> Vec<Rotation> {
+    rotations.iter().map(|rotation| {
+        let (axis, layer, quarter_turns) = decompose(rotation);
+        let face = face_map[followed_face(axis, layer)];
+        let new_axis = face / 2;
+        let new_layer = match layer {
+            0 | 1 => face % 2,
+            3 | 4 => 3 + face % 2,
+            _ => layer,
+        };
+        let mut new_quarter_turns = quarter_turns;
+        if face != followed_face(new_axis, new_layer) {
+            new_quarter_turns = 4 - new_quarter_turns;
+        }
+        if mirror {
+            new_quarter_turns = 4 - new_quarter_turns;
+        }
+        compose(new_axis, new_layer, new_quarter_turns).unwrap()
+    }).collect()
+}
+
+
+pub fn inverse(rotations: &[Rotation]) -> Vec<Rotation> {
+    rotations.iter().rev().map(|rotation| rotation.reverse()).collect()
+}
+
+// R U R' becomes L' U' L
+pub fn mirror_left_right(rotations: &[Rotation]) -> Vec<Rotation> {
+    map_faces(rotations, &[U, D, L, R, F, B], true)
+}
+
+// R U R' becomes R' U' R, F becomes B'
+pub fn mirror_front_back(rotations: &[Rotation]) -> Vec<Rotation> {
+    map_faces(rotations, &[U, D, R, L, B, F], true)
+}
+
+// The same turns written for a cube held after a whole cube rotation, so
+// `rotation` followed by the result does what the algorithm followed by
+// `rotation` does. R U R' with y becomes F U F'.
+pub fn rotated(rotations: &[Rotation], rotation: &Rotation) -> Vec<Rotation> {
+    // Where each face ends up after a quarter rotation
+    let (face_map, times) = match rotation {
+        Rotation::X => ([B, F, R, L, U, D], 1),
+        Rotation::X2 => ([B, F, R, L, U, D], 2),
+        Rotation::Xr => ([B, F, R, L, U, D], 3),
+        Rotation::Y => ([U, D, F, B, L, R], 1),
+        Rotation::Y2 => ([U, D, F, B, L, R], 2),
+        Rotation::Yr => ([U, D, F, B, L, R], 3),
+        Rotation::Z => ([R, L, D, U, F, B], 1),
+        Rotation::Z2 => ([R, L, D, U, F, B], 2),
+        Rotation::Zr => ([R, L, D, U, F, B], 3),
+        _ => panic!("{} is not a whole cube rotation", rotation),
+    };
+    let mut result = rotations.to_vec();
+    for _ in 0..times {
+        result = map_faces(&result, &face_map, false);
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::notation::{format_algorithm, parse_algorithm};
+
+    fn transformed(input: &str, transform: impl Fn(&[Rotation]) -> Vec<Rotation>) -> String {
+        format_algorithm(&transform(&parse_algorithm(input).unwrap()))
+    }
+
+    #[test]
+    fn inverse_undoes_the_algorithm() {
+        let rotations = parse_algorithm("R U2 F' M x Rw").unwrap();
+        let mut undone = rotations.clone();
+        undone.extend(inverse(&rotations));
+        assert_eq!(Cube::new().rotate_all(&undone), Cube::new());
+    }
+
+    #[test]
+    fn mirrors() {
+        assert_eq!(transformed("R U R'", mirror_left_right), "L' U' L");
+        assert_eq!(transformed("R U R'", mirror_front_back), "R' U' R");
+        assert_eq!(transformed("F", mirror_front_back), "B'");
+    }
+
+    #[test]
+    fn rotated_does_the_same_after_the_rotation() {
+        assert_eq!(transformed("R U R'", |rotations| rotated(rotations, &Rotation::Y)), "F U F'");
+        let rotations = parse_algorithm("R U F' D2 L B").unwrap();
+        for rotation in parse_algorithm("x y z x' y2 z'").unwrap() {
+            let mut first = vec![rotation.clone()];
+            first.extend(rotated(&rotations, &rotation));
+            let mut second = rotations.clone();
+            second.push(rotation.clone());
+            assert_eq!(Cube::new().rotate_all(&first), Cube::new().rotate_all(&second));
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use crate::cube::{Cube, Metric, Rotation};
+use crate::cubie::CubieCube;
+use crate::pattern_database::NibbleTable;
+use crate::simplify::simplify;
+use crate::solver::{relative_cubie, SearchLimits, SolveError, SolverConfig};
+use crate::table_file::{load_or_build, read_tables, write_tables, TableError, TableSource};
+
+
+pub const TWO_PHASE_KIND: &str = "two-phase";
+
+pub(crate) const SOLVED_SLICE: usize = 494;
+
+// Moves that keep the cube in <U, D, R2, L2, F2, B2>, as indices into
+// Metric::HalfTurn.rotations()
+pub(crate) const PHASE_2_MOVES: [usize; 10] = [0, 1, 6, 7, 12, 13, 14, 15, 16, 17];
+
+
+// Move tables for the coordinates of both phases and pruning tables with the
+// distance to the end of each phase for pairs of coordinates
+pub struct TwoPhaseTables {
+    pub metric: Metric,
+    rotations: Vec<Rotation>,
+    twist_moves: Vec<u16>,
+    flip_moves: Vec<u16>,
+    slice_moves: Vec<u16>,
+    corner_permutation_moves: Vec<u16>,
+    ud_edge_permutation_moves: Vec<u16>,
+    slice_permutation_moves: Vec<u16>,
+    twist_slice_distances: NibbleTable,
+    flip_slice_distances: NibbleTable,
+    twist_flip_distances: NibbleTable,
+    corner_slice_distances: NibbleTable,
+    edge_slice_distances: NibbleTable,
+}
+
+// Half turns are the last six moves of Metric::HalfTurn.rotations()
+fn move_cost(metric: Metric, rotation: usize) -> u8 {
+    if metric == Metric::QuarterTurn && rotation >= 12 { 2 } else { 1 }
+}
+
+// Where each of `moves` takes every value of a coordinate
+pub(crate) fn move_table(
+    size: usize,
+    moves: &[CubieCube],
+    set: impl Fn(&mut CubieCube, u16),
+    get: impl Fn(&CubieCube) -> u16,
+) -> Vec<u16> {
+    let mut table = vec![0; size * moves.len()];
+    let mut cube = CubieCube::new();
+    for value in 0..size {
+        set(&mut cube, value as u16);
+        for (i, rotation) in moves.iter().enumerate() {
+            table[value * moves.len() + i] = get(&cube.multiply(rotation));
+        }
+    }
+    table
+}
+
+impl TwoPhaseTables {
+    fn with_move_tables(metric: Metric) -> TwoPhaseTables {
+        let rotations = Metric::HalfTurn.rotations();
+        let moves: Vec<CubieCube> = rotations.iter().map(|rotation| CubieCube::new().rotate(rotation)).collect();
+        let phase_2_moves: Vec<CubieCube> = PHASE_2_MOVES.iter().map(|rotation| moves[*rotation].clone()).collect();
+
+        let twist_moves = move_table(2187, &moves, CubieCube::set_twist, CubieCube::twist);
+        let flip_moves = move_table(2048, &moves, CubieCube::set_flip, CubieCube::flip);
+        let slice_moves = move_table(495, &moves, CubieCube::set_slice, CubieCube::slice);
+        let corner_permutation_moves = move_table(
+            40320, &phase_2_moves, CubieCube::set_corner_permutation_rank, CubieCube::corner_permutation_rank,
+        );
+        let ud_edge_permutation_moves = move_table(
+            40320, &phase_2_moves, CubieCube::set_ud_edge_permutation_rank, CubieCube::ud_edge_permutation_rank,
+        );
+        let slice_permutation_moves = move_table(
+            24, &phase_2_moves, CubieCube::set_slice_permutation_rank, CubieCube::slice_permutation_rank,
+        );
+
+        TwoPhaseTables {
+            metric,
+            rotations,
+            twist_moves,
+            flip_moves,
+            slice_moves,
+            corner_permutation_moves,
+            ud_edge_permutation_moves,
+            slice_permutation_moves,
+            twist_slice_distances: NibbleTable::new(0),
+            flip_slice_distances: NibbleTable::new(0),
+            twist_flip_distances: NibbleTable::new(0),
+            corner_slice_distances: NibbleTable::new(0),
+            edge_slice_distances: NibbleTable::new(0),
+        }
+    }
+
+    pub fn new(metric: Metric) -> TwoPhaseTables {
+        let mut tables = TwoPhaseTables::with_move_tables(metric);
+        let costs: Vec<u8> = (0..18).map(|rotation| move_cost(tables.metric, rotation)).collect();
+        let phase_2_costs: Vec<u8> = PHASE_2_MOVES.iter().map(|rotation| costs[*rotation]).collect();
+        tables.twist_slice_distances = NibbleTable::new(495 * 2187);
+        tables.twist_slice_distances.fill(495 * 2187, &[SOLVED_SLICE * 2187], &costs, |index, rotation| {
+            tables.slice_moves[index / 2187 * 18 + rotation] as usize * 2187 + tables.twist_moves[index % 2187 * 18 + rotation] as usize
+        });
+        tables.flip_slice_distances = NibbleTable::new(495 * 2048);
+        tables.flip_slice_distances.fill(495 * 2048, &[SOLVED_SLICE * 2048], &costs, |index, rotation| {
+            tables.slice_moves[index / 2048 * 18 + rotation] as usize * 2048 + tables.flip_moves[index % 2048 * 18 + rotation] as usize
+        });
+        tables.twist_flip_distances = NibbleTable::new(2187 * 2048);
+        tables.twist_flip_distances.fill(2187 * 2048, &[0], &costs, |index, rotation| {
+            tables.twist_moves[index / 2048 * 18 + rotation] as usize * 2048 + tables.flip_moves[index % 2048 * 18 + rotation] as usize
+        });
+        tables.corner_slice_distances = NibbleTable::new(40320 * 24);
+        tables.corner_slice_distances.fill(40320 * 24, &[0], &phase_2_costs, |index, rotation| {
+            tables.corner_permutation_moves[index / 24 * 10 + rotation] as usize * 24
+                + tables.slice_permutation_moves[index % 24 * 10 + rotation] as usize
+        });
+        tables.edge_slice_distances = NibbleTable::new(40320 * 24);
+        tables.edge_slice_distances.fill(40320 * 24, &[0], &phase_2_costs, |index, rotation| {
+            tables.ud_edge_permutation_moves[index / 24 * 10 + rotation] as usize * 24
+                + tables.slice_permutation_moves[index % 24 * 10 + rotation] as usize
+        });
+        tables
+    }
+
+    pub fn load(path: &Path, metric: Metric) -> Result<TwoPhaseTables, TableError> {
+        let mut tables = TwoPhaseTables::with_move_tables(metric);
+        let lens = [495 * 2187, 495 * 2048, 2187 * 2048, 40320 * 24, 40320 * 24];
+        let mut distances = read_tables(path, TWO_PHASE_KIND, metric, &lens)?.into_iter();
+        tables.twist_slice_distances = distances.next().unwrap();
+        tables.flip_slice_distances = distances.next().unwrap();
+        tables.twist_flip_distances = distances.next().unwrap();
+        tables.corner_slice_distances = distances.next().unwrap();
+        tables.edge_slice_distances = distances.next().unwrap();
+        Ok(tables)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), TableError> {
+        let distances = [
+            &self.twist_slice_distances,
+            &self.flip_slice_distances,
+            &self.twist_flip_distances,
+            &self.corner_slice_distances,
+            &self.edge_slice_distances,
+        ];
+        write_tables(path, TWO_PHASE_KIND, self.metric, &distances)
+    }
+}
+
+
+struct Search<'a> {
+    tables: &'a TwoPhaseTables,
+    limits: SearchLimits<'a>,
+    // Cubes looked at in both phases so far
+    nodes: usize,
+    cube: CubieCube,
+    // Cost of a solution good enough to stop searching
+    good_enough: u8,
+    // Most a solution may cost
+    max_cost: u8,
+    phase_1: Vec<usize>,
+    phase_2: Vec<usize>,
+    best: Option<(u8, Vec<usize>)>,
+}
+
+impl Search<'_> {
+    fn cost(&self, rotation: usize) -> u8 {
+        move_cost(self.tables.metric, rotation)
+    }
+
+    // No two moves on the same face in a row, and of two opposite faces only
+    // the order starting with U, R or F
+    fn allowed(rotation: usize, last: Option<usize>) -> bool {
+        match last {
+            Some(last) => {
+                let (face, last_face) = (rotation % 6, last % 6);
+                face != last_face && !(face / 2 == last_face / 2 && face < last_face)
+            },
+            None => true,
+        }
+    }
+
+    fn done(&self) -> bool {
+        matches!(&self.best, Some((cost, _)) if *cost <= self.good_enough)
+    }
+
+    fn count_node(&mut self) -> Result<(), SolveError> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) {
+            self.limits.check(self.nodes, 0)?;
+        }
+        Ok(())
+    }
+
+    fn phase_1(&mut self, twist: usize, flip: usize, slice: usize, togo: u8) -> Result<(), SolveError> {
+        self.count_node()?;
+        let tables = self.tables;
+        if togo == 0 {
+            // A last move that stays in the subgroup means a shorter phase 1 was already tried
+            let last_stays = matches!(self.phase_1.last(), Some(last) if PHASE_2_MOVES.contains(last));
+            if twist == 0 && flip == 0 && slice == SOLVED_SLICE && !last_stays {
+                self.start_phase_2()?;
+            }
+            return Ok(());
+        }
+        let distance = tables.twist_slice_distances.get(slice * 2187 + twist)
+            .max(tables.flip_slice_distances.get(slice * 2048 + flip))
+            .max(tables.twist_flip_distances.get(twist * 2048 + flip));
+        if distance > togo {
+            return Ok(());
+        }
+        for rotation in 0..18 {
+            let cost = self.cost(rotation);
+            if cost > togo || !Search::allowed(rotation, self.phase_1.last().copied()) {
+                continue;
+            }
+            self.phase_1.push(rotation);
+            self.phase_1(
+                tables.twist_moves[twist * 18 + rotation] as usize,
+                tables.flip_moves[flip * 18 + rotation] as usize,
+                tables.slice_moves[slice * 18 + rotation] as usize,
+                togo - cost,
+            )?;
+            self.phase_1.pop();
+            if self.done() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    fn start_phase_2(&mut self) -> Result<(), SolveError> {
+        let mut cube = self.cube.clone();
+        for rotation in self.phase_1.iter() {
+            cube = cube.rotate(&self.tables.rotations[*rotation]);
+        }
+        let phase_1_cost: u8 = self.phase_1.iter().map(|rotation| self.cost(*rotation)).sum();
+        let limit = match &self.best {
+            Some((best, _)) if *best <= phase_1_cost + 1 => return Ok(()),
+            Some((best, _)) => best - 1 - phase_1_cost,
+            None => self.max_cost - phase_1_cost,
+        };
+
+        let corners = cube.corner_permutation_rank() as usize;
+        let edges = cube.ud_edge_permutation_rank() as usize;
+        let slice = cube.slice_permutation_rank() as usize;
+        let mut togo = self.phase_2_distance(corners, edges, slice);
+        while togo <= limit {
+            if self.phase_2(corners, edges, slice, togo)? {
+                let mut solution = self.phase_1.clone();
+                solution.extend(self.phase_2.iter().map(|rotation| PHASE_2_MOVES[*rotation]));
+                self.best = Some((phase_1_cost + togo, solution));
+                self.phase_2.clear();
+                return Ok(());
+            }
+            togo += 1;
+        }
+        Ok(())
+    }
+
+    fn phase_2_distance(&self, corners: usize, edges: usize, slice: usize) -> u8 {
+        self.tables.corner_slice_distances.get(corners * 24 + slice)
+            .max(self.tables.edge_slice_distances.get(edges * 24 + slice))
+    }
+
+    // Looks for a solution costing exactly `togo`
+    fn phase_2(&mut self, corners: usize, edges: usize, slice: usize, togo: u8) -> Result<bool, SolveError> {
+        self.count_node()?;
+        if togo == 0 {
+            return Ok(corners == 0 && edges == 0 && slice == 0);
+        }
+        if self.phase_2_distance(corners, edges, slice) > togo {
+            return Ok(false);
+        }
+        let tables = self.tables;
+        for (i, rotation) in PHASE_2_MOVES.iter().enumerate() {
+            let cost = self.cost(*rotation);
+            let last = self.phase_2.last().map(|last| PHASE_2_MOVES[*last]);
+            if cost > togo || !Search::allowed(*rotation, last) {
+                continue;
+            }
+            self.phase_2.push(i);
+            let found = self.phase_2(
+                tables.corner_permutation_moves[corners * 10 + i] as usize,
+                tables.ud_edge_permutation_moves[edges * 10 + i] as usize,
+                tables.slice_permutation_moves[slice * 10 + i] as usize,
+                togo - cost,
+            )?;
+            if found {
+                return Ok(true);
+            }
+            self.phase_2.pop();
+        }
+        Ok(false)
+    }
+}
+
+
+// The tables solve_cube_two_phase uses and where they came from. They are
+// loaded or built on first use, building takes a moment.
+pub fn two_phase_tables(metric: Metric) -> &'static (TwoPhaseTables, TableSource) {
+    static QUARTER_TURN_TABLES: OnceLock<(TwoPhaseTables, TableSource)> = OnceLock::new();
+    static HALF_TURN_TABLES: OnceLock<(TwoPhaseTables, TableSource)> = OnceLock::new();
+    let tables = match metric {
+        Metric::QuarterTurn => &QUARTER_TURN_TABLES,
+        Metric::HalfTurn => &HALF_TURN_TABLES,
+    };
+    tables.get_or_init(|| load_or_build(TWO_PHASE_KIND, metric, TwoPhaseTables::load, TwoPhaseTables::new))
+}
+
+// Kociemba's two-phase algorithm: phase 1 brings the cube into the subgroup
+// <U, D, R2, L2, F2, B2> (no twist, no flip, slice edges in the slice), phase 2
+// solves it using only those moves. Longer phase 1 solutions are tried until
+// the total is at most 21 moves (28 in the quarter turn metric) or cannot
+// get shorter. Fast, but not necessarily optimal.
+// Only solutions of at most `config.max_depth` moves are searched for, a
+// smaller max_depth than 21 (28) can make the search take much longer. A node
+// limit or timeout hit after a solution was found ends the search with that
+// solution. The memory budget and the moves of the config are not used.
+pub fn solve_cube_two_phase(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    metric: Metric,
+    config: &SolverConfig,
+) -> Result<Vec<Rotation>, SolveError> {
+    solve_cube_two_phase_with_tables(start_cube, end_cube, &two_phase_tables(metric).0, config)
+}
+
+pub fn solve_cube_two_phase_with_tables(
+    start_cube: &Cube,
+    end_cube: &Cube,
+    tables: &TwoPhaseTables,
+    config: &SolverConfig,
+) -> Result<Vec<Rotation>, SolveError> {
+    let cube = relative_cubie(start_cube, end_cube)?;
+
+    let max_cost = config.max_depth.min(u8::MAX as usize - 1) as u8;
+    let good_enough = if tables.metric == Metric::QuarterTurn { 28 } else { 21 };
+    let mut search = Search {
+        tables,
+        limits: SearchLimits::new(config),
+        nodes: 0,
+        cube: cube.clone(),
+        good_enough: good_enough.min(max_cost),
+        max_cost,
+        phase_1: vec![],
+        phase_2: vec![],
+        best: None,
+    };
+    let twist = cube.twist() as usize;
+    let flip = cube.flip() as usize;
+    let slice = cube.slice() as usize;
+    let mut depth = 0;
+    while depth <= max_cost {
+        if config.verbose {
+            println!("Searching phase 1 with {} moves", depth);
+        }
+        if let Err(error) = search.phase_1(twist, flip, slice, depth) {
+            if search.best.is_none() {
+                return Err(error);
+            }
+            break;
+        }
+        if search.done() || matches!(&search.best, Some((cost, _)) if *cost <= depth) {
+            break;
+        }
+        depth += 1;
+    }
+    let (_, solution) = search.best.ok_or(SolveError::DepthLimit { depth: config.max_depth })?;
+    // Phase 2 may start on the face phase 1 ended with, like R then R2
+    let rotations: Vec<Rotation> = solution.iter().map(|rotation| tables.rotations[*rotation].clone()).collect();
+    Ok(simplify(&rotations))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::notation::parse_algorithm;
+
+    const SCRAMBLE: &str = "R U2 F' D L2 B R' U F2 D' L B2 U R F' D2 L' B U2 R2";
+
+    #[test]
+    fn solves_within_21_moves() {
+        let start_cube = Cube::new().rotate_all(&parse_algorithm(SCRAMBLE).unwrap());
+        let config = SolverConfig::new().with_max_depth(21);
+        let solution = solve_cube_two_phase(&start_cube, &Cube::new(), Metric::HalfTurn, &config).unwrap();
+        assert!(solution.len() <= 21);
+        assert_eq!(start_cube.rotate_all(&solution), Cube::new());
+
+        let end_cube = Cube::new().rotate_all(&parse_algorithm("U R").unwrap());
+        let solution = solve_cube_two_phase(&start_cube, &end_cube, Metric::HalfTurn, &config).unwrap();
+        assert_eq!(start_cube.rotate_all(&solution), end_cube);
+    }
+
+    #[test]
+    fn stops_at_the_limits() {
+        let start_cube = Cube::new().rotate_all(&parse_algorithm(SCRAMBLE).unwrap());
+        let solve = |config: &SolverConfig| solve_cube_two_phase(&start_cube, &Cube::new(), Metric::HalfTurn, config);
+        assert_eq!(solve(&SolverConfig::new().with_max_depth(8)), Err(SolveError::DepthLimit { depth: 8 }));
+        assert_eq!(solve(&SolverConfig::new().with_max_nodes(100)), Err(SolveError::NodeLimit { nodes: 100 }));
+        assert!(matches!(solve(&SolverConfig::new().with_timeout(Duration::ZERO)), Err(SolveError::Timeout { .. })));
+    }
+}
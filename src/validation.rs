@@ -0,0 +1,169 @@
+use std::fmt;
+use crate::cube::{Cube, Rotation};
+use crate::coordinates::is_odd_permutation;
+use crate::cubie::{facelet_faces, corner_at, edge_at, CORNER_NAMES, EDGE_NAMES};
+use crate::facelets::{FACE_NAMES, FACE_SIDES};
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    WrongColorCount { color: u8, count: usize },
+    InvalidCenters,
+    InvalidCorner { position: usize },
+    InvalidEdge { position: usize },
+    DuplicateCorner { corner: usize },
+    DuplicateEdge { edge: usize },
+    CornerTwist { twist: u8 },
+    EdgeFlip,
+    PermutationParity,
+}
+
+// Colors are named by the face they belong to on the solved cube
+fn color_name(color: u8) -> String {
+    match FACE_SIDES.iter().position(|side| *side == color as usize) {
+        Some(face) => FACE_NAMES[face].to_string(),
+        None => format!("#{}", color),
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::WrongColorCount { color, count } => {
+                write!(f, "color {} appears {} times, expected 9", color_name(*color), count)
+            },
+            ValidationError::InvalidCenters => write!(f, "the centers are not arranged like on a real cube"),
+            ValidationError::InvalidCorner { position } => {
+                write!(f, "the corner at {} has colors no corner has", CORNER_NAMES[*position])
+            },
+            ValidationError::InvalidEdge { position } => {
+                write!(f, "the edge at {} has colors no edge has", EDGE_NAMES[*position])
+            },
+            ValidationError::DuplicateCorner { corner } => write!(f, "the {} corner appears more than once", CORNER_NAMES[*corner]),
+            ValidationError::DuplicateEdge { edge } => write!(f, "the {} edge appears more than once", EDGE_NAMES[*edge]),
+            ValidationError::CornerTwist { twist } => {
+                write!(f, "the corner twists add up to {} instead of a multiple of 3, a corner is twisted", twist)
+            },
+            ValidationError::EdgeFlip => write!(f, "one edge is flipped"),
+            ValidationError::PermutationParity => write!(f, "two pieces are swapped"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+
+// Center colors of the 24 ways the solved cube can be held
+fn center_orientations() -> Vec<[u8; 6]> {
+    let mut orientations = vec![];
+    // Each of these brings a different side to the top
+    for top in [vec![], vec![Rotation::X], vec![Rotation::X2], vec![Rotation::Xr], vec![Rotation::Z], vec![Rotation::Zr]] {
+        let mut cube = Cube::new().rotate_all(&top);
+        for _ in 0..4 {
+            orientations.push([0, 1, 2, 3, 4, 5].map(|side| cube.get_at(side, 4)));
+            cube = cube.rotate_all(&[Rotation::Y]);
+        }
+    }
+    orientations
+}
+
+
+impl Cube {
+    // Checks that the cube could be reached by turning a solved cube, from the
+    // coarsest problem (a sticker of the wrong color) to the finest (a swap).
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut counts = [0; 256];
+        for side in 0..6 {
+            for face in 0..9 {
+                counts[self.get_at(side, face) as usize] += 1;
+            }
+        }
+        for (color, count) in counts.iter().enumerate() {
+            if (color < 6 && *count != 9) || (color >= 6 && *count != 0) {
+                return Err(ValidationError::WrongColorCount { color: color as u8, count: *count });
+            }
+        }
+
+        let centers = [0, 1, 2, 3, 4, 5].map(|side| self.get_at(side, 4));
+        if !center_orientations().contains(&centers) {
+            return Err(ValidationError::InvalidCenters);
+        }
+
+        let faces = facelet_faces(self);
+        let mut corners_seen = [false; 8];
+        let mut corner_permutation = vec![];
+        let mut twist = 0;
+        for position in 0..8 {
+            let (corner, orientation) = corner_at(&faces, position).ok_or(ValidationError::InvalidCorner { position })?;
+            if corners_seen[corner as usize] {
+                return Err(ValidationError::DuplicateCorner { corner: corner as usize });
+            }
+            corners_seen[corner as usize] = true;
+            corner_permutation.push(corner);
+            twist += orientation;
+        }
+
+        let mut edges_seen = [false; 12];
+        let mut edge_permutation = vec![];
+        let mut flip = 0;
+        for position in 0..12 {
+            let (edge, orientation) = edge_at(&faces, position).ok_or(ValidationError::InvalidEdge { position })?;
+            if edges_seen[edge as usize] {
+                return Err(ValidationError::DuplicateEdge { edge: edge as usize });
+            }
+            edges_seen[edge as usize] = true;
+            edge_permutation.push(edge);
+            flip += orientation;
+        }
+
+        if twist % 3 != 0 {
+            return Err(ValidationError::CornerTwist { twist: twist % 3 });
+        }
+        if flip % 2 != 0 {
+            return Err(ValidationError::EdgeFlip);
+        }
+        if is_odd_permutation(&corner_permutation) != is_odd_permutation(&edge_permutation) {
+            return Err(ValidationError::PermutationParity);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cubie::CubieCube;
+    use crate::notation::parse_algorithm;
+
+    #[test]
+    fn accepts_reachable_cubes() {
+        assert_eq!(Cube::new().validate(), Ok(()));
+        let scramble = parse_algorithm("R U2 F' D L2 B x M' Uw").unwrap();
+        assert_eq!(Cube::new().rotate_all(&scramble).validate(), Ok(()));
+    }
+
+    #[test]
+    fn tells_what_is_wrong() {
+        let mut flipped = CubieCube::new();
+        flipped.edge_orientation[0] = 1;
+        assert_eq!(flipped.to_cube().validate(), Err(ValidationError::EdgeFlip));
+
+        let mut twisted = CubieCube::new();
+        twisted.corner_orientation[0] = 1;
+        assert_eq!(twisted.to_cube().validate(), Err(ValidationError::CornerTwist { twist: 1 }));
+
+        let mut swapped = CubieCube::new();
+        swapped.edge_permutation.swap(0, 1);
+        assert_eq!(swapped.to_cube().validate(), Err(ValidationError::PermutationParity));
+
+        let mut recolored = Cube::new();
+        recolored.set_at(0, 0, 5);
+        assert_eq!(recolored.validate(), Err(ValidationError::WrongColorCount { color: 0, count: 8 }));
+
+        let mut mirrored = Cube::new();
+        mirrored.set_at(0, 4, 5);
+        mirrored.set_at(5, 4, 0);
+        assert_eq!(mirrored.validate(), Err(ValidationError::InvalidCenters));
+    }
+}